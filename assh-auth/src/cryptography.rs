@@ -0,0 +1,66 @@
+//! Helpers for the signatures encountered through the authentication methods.
+
+use ssh_packet::{arch, binrw};
+
+/// The data that gets _signed_ and _verified_ to prove the possession of the said host key in
+/// the `hostbased` authentication method, computed from the concatenation of the following.
+///
+/// see <https://datatracker.ietf.org/doc/html/rfc4252#section-9>.
+#[binrw::binwrite]
+#[derive(Debug, Clone)]
+#[bw(big)]
+pub struct HostbasedSignature<'s> {
+    /// The session identifier issued by the key-exchange.
+    pub session_id: &'s arch::Bytes,
+
+    #[bw(calc = 50)]
+    magic: u8,
+
+    /// Username for the auth request.
+    pub username: &'s arch::StringUtf8,
+
+    /// Service name to query.
+    pub service_name: &'s arch::StringAscii,
+
+    #[bw(calc = "hostbased".into())]
+    method: arch::StringUtf8,
+
+    /// Host key algorithm's name.
+    pub algorithm: &'s arch::Bytes,
+
+    /// Host key blob.
+    pub host_key: &'s arch::Bytes,
+
+    /// Client host name expressed as the FQDN.
+    pub client_fqdn: &'s arch::StringAscii,
+
+    /// User name on the client host.
+    pub client_username: &'s arch::StringUtf8,
+}
+
+impl HostbasedSignature<'_> {
+    /// Encode the structure into the data to be _signed_ and _verified_.
+    pub fn to_bytes(&self) -> signature::Result<Vec<u8>> {
+        use binrw::BinWrite;
+
+        let mut buffer = Vec::new();
+        self.write(&mut std::io::Cursor::new(&mut buffer))
+            .map_err(signature::Error::from_source)?;
+
+        Ok(buffer)
+    }
+
+    /// Verify the structure against the provided `signature` with the `key`.
+    pub fn verify<S, K: signature::Verifier<S>>(
+        &self,
+        key: &K,
+        signature: &S,
+    ) -> signature::Result<()> {
+        K::verify(key, &self.to_bytes()?, signature)
+    }
+
+    /// Sign the structure with the provided `key` to produce the `signature`.
+    pub fn sign<S, K: signature::Signer<S>>(&self, key: &K) -> signature::Result<S> {
+        K::try_sign(key, &self.to_bytes()?)
+    }
+}
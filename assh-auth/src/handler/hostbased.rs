@@ -0,0 +1,56 @@
+//! The `hostbased` authentication method.
+
+use futures::Future;
+
+#[doc(no_inline)]
+pub use ssh_key::PublicKey;
+
+/// The response to the authentication request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Response {
+    /// _Accept_ the authentication request.
+    Accept,
+
+    /// _Reject_ the authentication request.
+    Reject,
+}
+
+impl_into_future!(Response);
+
+/// An interface to the `hostbased` authentication method.
+pub trait Hostbased: Send + Sync {
+    /// Process the authentication request, from `client_user` on the host `client_fqdn`,
+    /// which proved the possession of the `host_key`.
+    fn process(
+        &mut self,
+        user: String,
+        host_key: PublicKey,
+        client_fqdn: String,
+        client_user: String,
+    ) -> impl Future<Output = Response> + Send;
+}
+
+/// An implementation for closures.
+impl<T, F> Hostbased for T
+where
+    T: FnMut(String, PublicKey, String, String) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Response>,
+    F::IntoFuture: Send,
+{
+    fn process(
+        &mut self,
+        user: String,
+        host_key: PublicKey,
+        client_fqdn: String,
+        client_user: String,
+    ) -> impl Future<Output = Response> + Send {
+        (self)(user, host_key, client_fqdn, client_user).into_future()
+    }
+}
+
+/// A default implementation of the method that rejects all requests.
+impl Hostbased for () {
+    async fn process(&mut self, _: String, _: PublicKey, _: String, _: String) -> Response {
+        Response::Reject
+    }
+}
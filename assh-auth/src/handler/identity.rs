@@ -0,0 +1,102 @@
+//! The _identity_ of the authenticated user, provided to the caller alongside the service.
+
+use std::any::{Any, TypeId};
+
+use futures::Future;
+use hashbrown::HashMap;
+use ssh_key::{Fingerprint, HashAlg, PublicKey};
+
+use super::Method;
+
+/// A type-indexed map of values, for the [`Identify`] hook to attach context,
+/// such as an _uid_ or _roles_, to the [`Identity`].
+#[derive(Default)]
+pub struct Context(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl Context {
+    /// Insert a value in the context, returning the previous value of the same type, if any.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.0
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Get a reference to the value of type `T` in the context, if any.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Remove the value of type `T` from the context, returning it if any.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.0
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+}
+
+impl std::fmt::Debug for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Context").finish_non_exhaustive()
+    }
+}
+
+/// The identity of an authenticated user.
+#[derive(Debug)]
+pub struct Identity {
+    /// The name of the user.
+    pub username: String,
+
+    /// The methods completed by the user, in order, the last one completing the authentication.
+    pub methods: Vec<Method>,
+
+    /// The last key the user authenticated with, using either the `publickey` or `hostbased` method.
+    pub key: Option<PublicKey>,
+
+    /// The context attached by the [`Identify`] hook.
+    pub context: Context,
+}
+
+impl Identity {
+    /// The method that completed the authentication.
+    pub fn method(&self) -> Option<Method> {
+        self.methods.last().copied()
+    }
+
+    /// The `SHA256` fingerprint of the [`Self::key`], if any.
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        self.key
+            .as_ref()
+            .map(|key| key.fingerprint(HashAlg::Sha256))
+    }
+}
+
+impl_into_future!(Identity);
+
+/// An interface to attach context to the [`Identity`] of the authenticated user.
+pub trait Identify: Send + Sync {
+    /// Process the identity of the authenticated user, before the service is started.
+    fn identify(&mut self, identity: Identity) -> impl Future<Output = Identity> + Send;
+}
+
+/// An implementation for closures.
+impl<T, F> Identify for T
+where
+    T: FnMut(Identity) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Identity>,
+    F::IntoFuture: Send,
+{
+    fn identify(&mut self, identity: Identity) -> impl Future<Output = Identity> + Send {
+        (self)(identity).into_future()
+    }
+}
+
+/// A default implementation that leaves the identity untouched.
+impl Identify for () {
+    async fn identify(&mut self, identity: Identity) -> Identity {
+        identity
+    }
+}
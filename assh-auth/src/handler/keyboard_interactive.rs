@@ -0,0 +1,82 @@
+//! The `keyboard-interactive` authentication method.
+
+use futures::Future;
+
+/// A prompt to be displayed to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    /// The text of the prompt.
+    pub prompt: String,
+
+    /// Whether the user's input should be echoed as it is typed.
+    pub echo: bool,
+}
+
+impl Prompt {
+    /// Create a new [`Prompt`] from it's text and echo flag.
+    pub fn new(prompt: impl Into<String>, echo: bool) -> Self {
+        Self {
+            prompt: prompt.into(),
+            echo,
+        }
+    }
+}
+
+/// The response to the authentication request, or to the user's answers.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Response {
+    /// _Accept_ the authentication request.
+    Accept,
+
+    /// _Challenge_ the user with a set of prompts, their answers
+    /// are then provided in a subsequent call to [`KeyboardInteractive::process`].
+    Challenge {
+        /// The name of the challenge, displayed to the user.
+        name: String,
+
+        /// The instructions of the challenge, displayed to the user.
+        instruction: String,
+
+        /// The prompts the user is asked to answer, possibly none.
+        prompts: Vec<Prompt>,
+    },
+
+    /// _Reject_ the authentication request.
+    Reject,
+}
+
+impl_into_future!(Response);
+
+/// An interface to the `keyboard-interactive` authentication method.
+pub trait KeyboardInteractive: Send + Sync {
+    /// Process the authentication request, with `responses` being [`None`] on the initial request,
+    /// and the user's answers to the prompts of the last [`Response::Challenge`] afterwards.
+    fn process(
+        &mut self,
+        user: String,
+        responses: Option<Vec<String>>,
+    ) -> impl Future<Output = Response> + Send;
+}
+
+/// An implementation for closures.
+impl<T, F> KeyboardInteractive for T
+where
+    T: FnMut(String, Option<Vec<String>>) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Response>,
+    F::IntoFuture: Send,
+{
+    fn process(
+        &mut self,
+        user: String,
+        responses: Option<Vec<String>>,
+    ) -> impl Future<Output = Response> + Send {
+        (self)(user, responses).into_future()
+    }
+}
+
+/// A default implementation of the method that rejects all requests.
+impl KeyboardInteractive for () {
+    async fn process(&mut self, _: String, _: Option<Vec<String>>) -> Response {
+        Response::Reject
+    }
+}
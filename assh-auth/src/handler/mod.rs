@@ -1,22 +1,53 @@
 //! Authentication _handling_ mechanics.
 
-use assh::{service::Handler, side::Side, Error, Result, Session};
+use std::time::{Duration, Instant};
+
+use assh::{
+    algorithm::{key, Key},
+    service::Handler,
+    side::Side,
+    Result, Session,
+};
 use enumset::EnumSet;
-use futures::{AsyncBufRead, AsyncWrite};
+use futures::{AsyncBufRead, AsyncWrite, FutureExt};
 use ssh_key::{public::PublicKey, Signature};
 use ssh_packet::{
     arch::{NameList, StringAscii, StringUtf8},
     cryptography::PublickeySignature,
     trans::DisconnectReason,
-    userauth,
+    userauth, Packet,
 };
 
+/// Implement [`std::future::IntoFuture`] for the response of a hook,
+/// so that closures implementing it may return either the response or a future of it.
+macro_rules! impl_into_future {
+    ($response:ty) => {
+        /// Resolves immediately, allowing closures to return the value without a future.
+        impl std::future::IntoFuture for $response {
+            type Output = Self;
+            type IntoFuture = std::future::Ready<Self>;
+
+            fn into_future(self) -> Self::IntoFuture {
+                std::future::ready(self)
+            }
+        }
+    };
+}
+
 mod method;
-use method::Method;
+pub use method::Method;
+
+mod state;
+
+use crate::cryptography::HostbasedSignature;
 
+pub mod hostbased;
+pub mod identity;
+pub mod keyboard_interactive;
 pub mod none;
 pub mod password;
 pub mod publickey;
+pub mod required;
 
 #[derive(Debug, PartialEq)]
 enum Attempt {
@@ -24,48 +55,68 @@ enum Attempt {
     Partial,
     Failure,
     Continue,
+
+    /// The attempt failed, abandoned by the client with a new request, which is answered instead.
+    Abandoned,
 }
 
 /// The authentication service [`Handler`] for sessions.
 #[derive(Debug)]
-pub struct Auth<H, N = (), P = (), PK = ()> {
+pub struct Auth<H, N = (), P = (), PK = (), KI = (), HB = (), RM = (), ID = ()> {
     banner: Option<StringUtf8>,
-    // TODO: Add a total attempts counter, to disconnect when exceeded.
-    // TODO: Retain methods per user-basis, because each user can attempt all the methods.
+    max_attempts: usize,
+    grace_time: Option<Duration>,
     methods: EnumSet<Method>,
+    state: state::State,
 
     handler: H,
 
     none: N,
     password: P,
     publickey: PK,
+    keyboard_interactive: KI,
+    hostbased: HB,
+    required: RM,
+    identify: ID,
 }
 
 impl<H> Auth<H>
 where
     H: Handler,
 {
-    /// Create an [`Auth`] layer, rejecting all authentication by default.
+    /// Create an [`Auth`] layer, rejecting all authentication by default,
+    /// and disconnecting after 6 failed attempts, see [`Auth::max_attempts`].
     pub fn new(service: H) -> Self {
         Self {
             banner: Default::default(),
+            max_attempts: 6,
+            grace_time: None,
             methods: Method::None.into(), // always insert the `none` method
+            state: Default::default(),
 
             handler: service,
 
             none: (),
             password: (),
             publickey: (),
+            keyboard_interactive: (),
+            hostbased: (),
+            required: (),
+            identify: (),
         }
     }
 }
 
-impl<H, N, P, PK> Auth<H, N, P, PK>
+impl<H, N, P, PK, KI, HB, RM, ID> Auth<H, N, P, PK, KI, HB, RM, ID>
 where
     H: Handler,
     N: none::None,
     P: password::Password,
     PK: publickey::Publickey,
+    KI: keyboard_interactive::KeyboardInteractive,
+    HB: hostbased::Hostbased,
+    RM: required::Required,
+    ID: identity::Identify,
 {
     /// Set the authentication banner text to be displayed upon authentication (the string should be `\r\n` terminated).
     pub fn banner(mut self, banner: impl Into<StringUtf8>) -> Self {
@@ -74,26 +125,67 @@ where
         self
     }
 
+    /// Set the maximum number of failed attempts, across all users, before disconnecting
+    /// the session with `SSH_DISCONNECT_NO_MORE_AUTH_METHODS_AVAILABLE`, akin to OpenSSH's `MaxAuthTries`
+    /// (the `none` method is not counted, as it is used to query the available methods).
+    ///
+    /// # Breaking change
+    /// This defaults to 6 attempts, where previous versions allowed an unlimited number of them,
+    /// so clients retrying more than that are now disconnected,
+    /// set it to [`usize::MAX`] to restore the previous behavior.
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+
+        self
+    }
+
+    /// Set the deadline for the user to authenticate, from the start of the authentication
+    /// service, after which the session is disconnected with `SSH_DISCONNECT_BY_APPLICATION`,
+    /// akin to OpenSSH's `LoginGraceTime` (disabled by default, [`None`] disables the deadline).
+    ///
+    /// # Note
+    /// The deadline is only checked while waiting for the client's messages,
+    /// not while the hooks are processing an attempt.
+    pub fn grace_time(mut self, grace_time: impl Into<Option<Duration>>) -> Self {
+        self.grace_time = grace_time.into();
+
+        self
+    }
+
     /// Set the authentication handler for the `none` method.
-    pub fn none(self, none: impl none::None) -> Auth<H, impl none::None, P, PK> {
+    pub fn none(self, none: impl none::None) -> Auth<H, impl none::None, P, PK, KI, HB, RM, ID> {
         let Self {
             banner,
+            max_attempts,
+            grace_time,
             mut methods,
+            state,
             handler,
             none: _,
             password,
             publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
         } = self;
 
         methods |= Method::None;
 
         Auth {
             banner,
+            max_attempts,
+            grace_time,
             methods,
+            state,
             handler,
             none,
             password,
             publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
         }
     }
 
@@ -101,25 +193,39 @@ where
     pub fn password(
         self,
         password: impl password::Password,
-    ) -> Auth<H, N, impl password::Password, PK> {
+    ) -> Auth<H, N, impl password::Password, PK, KI, HB, RM, ID> {
         let Self {
             banner,
+            max_attempts,
+            grace_time,
             mut methods,
+            state,
             handler,
             none,
             password: _,
             publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
         } = self;
 
         methods |= Method::Password;
 
         Auth {
             banner,
+            max_attempts,
+            grace_time,
             methods,
+            state,
             handler,
             none,
             password,
             publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
         }
     }
 
@@ -127,31 +233,362 @@ where
     pub fn publickey(
         self,
         publickey: impl publickey::Publickey,
-    ) -> Auth<H, N, P, impl publickey::Publickey> {
+    ) -> Auth<H, N, P, impl publickey::Publickey, KI, HB, RM, ID> {
         let Self {
             banner,
+            max_attempts,
+            grace_time,
             mut methods,
+            state,
             handler,
             none,
             password,
             publickey: _,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
         } = self;
 
         methods |= Method::Publickey;
 
         Auth {
             banner,
+            max_attempts,
+            grace_time,
             methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
+        }
+    }
+
+    /// Set the authentication handler for the `keyboard-interactive` method.
+    pub fn keyboard_interactive(
+        self,
+        keyboard_interactive: impl keyboard_interactive::KeyboardInteractive,
+    ) -> Auth<H, N, P, PK, impl keyboard_interactive::KeyboardInteractive, HB, RM, ID> {
+        let Self {
+            banner,
+            max_attempts,
+            grace_time,
+            mut methods,
+            state,
             handler,
             none,
             password,
             publickey,
+            keyboard_interactive: _,
+            hostbased,
+            required,
+            identify,
+        } = self;
+
+        methods |= Method::KeyboardInteractive;
+
+        Auth {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
+        }
+    }
+
+    /// Set the authentication handler for the `hostbased` method.
+    pub fn hostbased(
+        self,
+        hostbased: impl hostbased::Hostbased,
+    ) -> Auth<H, N, P, PK, KI, impl hostbased::Hostbased, RM, ID> {
+        let Self {
+            banner,
+            max_attempts,
+            grace_time,
+            mut methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased: _,
+            required,
+            identify,
+        } = self;
+
+        methods |= Method::Hostbased;
+
+        Auth {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
+        }
+    }
+
+    /// Set the chains of methods required to authenticate, akin to OpenSSH's `AuthenticationMethods`,
+    /// completing a method of a chain results in a _partial success_ until the chain is exhausted.
+    pub fn required(
+        self,
+        required: impl required::Required,
+    ) -> Auth<H, N, P, PK, KI, HB, impl required::Required, ID> {
+        let Self {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required: _,
+            identify,
+        } = self;
+
+        Auth {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
+        }
+    }
+
+    /// Set the hook attaching context, such as an _uid_ or _roles_, to the [`identity::Identity`]
+    /// of the authenticated user, which is returned alongside the service.
+    pub fn identify(
+        self,
+        identify: impl identity::Identify,
+    ) -> Auth<H, N, P, PK, KI, HB, RM, impl identity::Identify> {
+        let Self {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify: _,
+        } = self;
+
+        Auth {
+            banner,
+            max_attempts,
+            grace_time,
+            methods,
+            state,
+            handler,
+            none,
+            password,
+            publickey,
+            keyboard_interactive,
+            hostbased,
+            required,
+            identify,
+        }
+    }
+
+    /// Take the state of the user `username` out of the session's state,
+    /// which is reset when the username changes mid-authentication.
+    fn take_user(&mut self, username: &str) -> state::User {
+        match self.state.user.take() {
+            Some(user) if user.name == username => user,
+            previous => {
+                if let Some(previous) = previous {
+                    tracing::debug!(
+                        "Username changed from `{}` to `{username}`, resetting authentication state",
+                        previous.name
+                    );
+                }
+
+                state::User::new(
+                    username.into(),
+                    self.methods,
+                    self.required.chains(username.into()),
+                )
+            }
+        }
+    }
+
+    /// Receive the next message from the client, disconnecting the session
+    /// with `SSH_DISCONNECT_BY_APPLICATION` once the grace time is exceeded.
+    ///
+    /// # Note
+    /// The deadline is only raced against the cancel-safe [`Session::readable`],
+    /// so that a message is never dropped while partially received.
+    async fn recv<IO: AsyncBufRead + AsyncWrite + Unpin, S: Side>(
+        &self,
+        session: &mut Session<IO, S>,
+    ) -> Result<Packet> {
+        if let Some(deadline) = self.state.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            let expired = futures::select_biased! {
+                res = session.readable().fuse() => res.map(|_| false)?,
+                _ = futures_time::task::sleep(remaining.into()).fuse() => true,
+            };
+
+            if expired {
+                return Err(session
+                    .disconnect(
+                        DisconnectReason::ByApplication,
+                        "Authentication grace time exceeded",
+                    )
+                    .await
+                    .into());
+            }
+        }
+
+        session.recv().await
+    }
+
+    /// Run the authentication exchange until the user is authenticated.
+    async fn authenticate<IO: AsyncBufRead + AsyncWrite + Unpin, S: Side>(
+        &mut self,
+        session: &mut Session<IO, S>,
+    ) -> Result<identity::Identity> {
+        if let Some(message) = self.banner.take() {
+            session
+                .send(&userauth::Banner {
+                    message,
+                    ..Default::default()
+                })
+                .await?;
+        }
+
+        loop {
+            let request = match self.state.pending.take() {
+                Some(request) => Ok(request),
+                None => self.recv(session).await?.to(),
+            };
+
+            if let Ok(userauth::Request {
+                username,
+                service_name,
+                method,
+            }) = request
+            {
+                let kind = *method.as_ref();
+                let mut user = self.take_user(&username);
+
+                let attempt = if user.allowed().contains(kind) && user.methods.remove(kind) {
+                    match self
+                        .handle_attempt(session, &mut user, username, method, &service_name)
+                        .await?
+                    {
+                        Attempt::Success => user.advance(kind),
+                        attempt => attempt,
+                    }
+                } else {
+                    Attempt::Failure
+                };
+
+                let continue_with = NameList::new(user.allowed());
+
+                if attempt == Attempt::Success {
+                    break if &*service_name == H::SERVICE_NAME {
+                        let identity = self.identify.identify(user.into_identity()).await;
+                        session.send(&userauth::Success).await?;
+
+                        Ok(identity)
+                    } else {
+                        Err(session
+                            .disconnect(
+                                DisconnectReason::ServiceNotAvailable,
+                                "Requested service is unknown",
+                            )
+                            .await
+                            .into())
+                    };
+                }
+
+                self.state.user = Some(user);
+
+                match attempt {
+                    Attempt::Failure | Attempt::Partial | Attempt::Abandoned => {
+                        if attempt != Attempt::Partial && kind != Method::None {
+                            self.state.attempts += 1;
+
+                            if self.state.attempts >= self.max_attempts {
+                                break Err(session
+                                    .disconnect(
+                                        DisconnectReason::NoMoreAuthMethodsAvailable,
+                                        "Too many authentication failures",
+                                    )
+                                    .await
+                                    .into());
+                            }
+                        }
+
+                        if attempt != Attempt::Abandoned {
+                            session
+                                .send(&userauth::Failure {
+                                    continue_with,
+                                    partial_success: (attempt == Attempt::Partial).into(),
+                                })
+                                .await?;
+                        }
+                    }
+                    Attempt::Success | Attempt::Continue => (),
+                }
+            } else {
+                break Err(session
+                    .disconnect(
+                        DisconnectReason::ProtocolError,
+                        format!(
+                            "Unexpected message in the context of the `{}` service request",
+                            crate::SERVICE_NAME
+                        ),
+                    )
+                    .await
+                    .into());
+            }
         }
     }
 
     async fn handle_attempt<IO: AsyncBufRead + AsyncWrite + Unpin, S: Side>(
         &mut self,
         session: &mut Session<IO, S>,
+        user: &mut state::User,
         username: StringUtf8,
         method: userauth::Method,
         service_name: &StringAscii,
@@ -163,7 +600,7 @@ where
                     username.as_str()
                 );
 
-                match self.none.process(username.to_string()) {
+                match self.none.process(username.to_string()).await {
                     none::Response::Accept => Attempt::Success,
                     none::Response::Reject => Attempt::Failure,
                 }
@@ -186,7 +623,7 @@ where
                 match signature {
                     None => {
                         // Authentication has not actually been attempted, so we allow it again.
-                        self.methods |= Method::Publickey;
+                        user.methods |= Method::Publickey;
 
                         if key.is_ok() {
                             session.send(&userauth::PkOk { blob, algorithm }).await?;
@@ -209,9 +646,14 @@ where
                             if message
                                 .verify(&key, &Signature::try_from(signature.as_ref())?)
                                 .is_ok()
-                                && self.publickey.process(username.to_string(), key)
+                                && self
+                                    .publickey
+                                    .process(username.to_string(), key.clone())
+                                    .await
                                     == publickey::Response::Accept
                             {
+                                user.key = Some(key);
+
                                 Attempt::Success
                             } else {
                                 // TODO: Does a faked signature needs to cause disconnection ?
@@ -230,14 +672,18 @@ where
                     username.as_str()
                 );
 
-                match self.password.process(
-                    username.into_string(),
-                    password.into_string(),
-                    new.map(StringUtf8::into_string),
-                ) {
+                match self
+                    .password
+                    .process(
+                        username.into_string(),
+                        password.into_string(),
+                        new.map(StringUtf8::into_string),
+                    )
+                    .await
+                {
                     password::Response::Accept => Attempt::Success,
                     password::Response::PasswordExpired { prompt } => {
-                        self.methods |= Method::Password;
+                        user.methods |= Method::Password;
 
                         session
                             .send(&userauth::PasswdChangereq {
@@ -252,24 +698,157 @@ where
                 }
             }
 
-            userauth::Method::Hostbased { .. } => {
-                // TODO: Add hostbased authentication.
-                todo!("Server-side `hostbased` method is not implemented")
+            userauth::Method::Hostbased {
+                algorithm,
+                host_key,
+                client_fqdn,
+                username: client_username,
+                signature,
+            } => {
+                tracing::debug!(
+                    "Attempt using method `hostbased` (algorithm: {}) for user `{}` from `{}@{}`",
+                    std::str::from_utf8(&algorithm).unwrap_or("unknown"),
+                    username.as_str(),
+                    client_username.as_str(),
+                    client_fqdn.as_str(),
+                );
+
+                let key = PublicKey::from_bytes(&host_key).ok();
+                let advertised = std::str::from_utf8(&algorithm)
+                    .ok()
+                    .and_then(|algorithm| Key::new(algorithm).ok());
+                let signature = Signature::try_from(signature.as_ref()).ok();
+
+                // The key may sign with any compatible algorithm, such as `rsa-sha2-256`
+                // for an RSA key, the signature being verified with the advertised one.
+                match (key, advertised, signature) {
+                    (Some(key), Some(advertised), Some(signature))
+                        if key::is_compatible(&key.algorithm(), &advertised)
+                            && signature.algorithm() == advertised =>
+                    {
+                        let message = HostbasedSignature {
+                            session_id: &session.session_id().unwrap_or_default().into(),
+                            username: &username,
+                            service_name,
+                            algorithm: &algorithm,
+                            host_key: &host_key,
+                            client_fqdn: &client_fqdn,
+                            client_username: &client_username,
+                        };
+
+                        if message.verify(&key, &signature).is_ok()
+                            && self
+                                .hostbased
+                                .process(
+                                    username.to_string(),
+                                    key.clone(),
+                                    client_fqdn.to_string(),
+                                    client_username.to_string(),
+                                )
+                                .await
+                                == hostbased::Response::Accept
+                        {
+                            user.key = Some(key);
+
+                            Attempt::Success
+                        } else {
+                            Attempt::Failure
+                        }
+                    }
+                    _ => Attempt::Failure,
+                }
             }
 
-            userauth::Method::KeyboardInteractive { .. } => {
-                // TODO: Add keyboard-interactive authentication.
-                todo!("Server-side `keyboard-interactive` method is not implemented")
+            userauth::Method::KeyboardInteractive { submethods, .. } => {
+                tracing::debug!(
+                    "Attempt using method `keyboard-interactive` (submethods: `{}`) for user `{}`",
+                    submethods.as_str(),
+                    username.as_str()
+                );
+
+                let mut responses = None;
+
+                loop {
+                    match self
+                        .keyboard_interactive
+                        .process(username.to_string(), responses.take())
+                        .await
+                    {
+                        keyboard_interactive::Response::Accept => break Attempt::Success,
+                        keyboard_interactive::Response::Challenge {
+                            name,
+                            instruction,
+                            prompts,
+                        } => {
+                            let count = prompts.len();
+
+                            session
+                                .send(&userauth::InfoRequest {
+                                    name: name.into(),
+                                    instruction: instruction.into(),
+                                    prompts: prompts
+                                        .into_iter()
+                                        .map(|prompt| userauth::InfoRequestPrompt {
+                                            prompt: prompt.prompt.into(),
+                                            echo: prompt.echo.into(),
+                                        })
+                                        .collect(),
+                                    language: Default::default(),
+                                })
+                                .await?;
+
+                            let packet = self.recv(session).await?;
+
+                            match packet.to::<userauth::InfoResponse>() {
+                                Ok(userauth::InfoResponse { responses: answers })
+                                    if answers.len() == count =>
+                                {
+                                    responses = Some(
+                                        answers.into_iter().map(StringUtf8::into_string).collect(),
+                                    );
+                                }
+                                // The client may abandon the exchange with a new request,
+                                // as described in RFC4256 section 3.3.
+                                Err(_) if packet.to::<userauth::Request>().is_ok() => {
+                                    tracing::debug!(
+                                        "Attempt using method `keyboard-interactive` abandoned by user `{}`",
+                                        username.as_str()
+                                    );
+
+                                    self.state.pending = packet.to().ok();
+
+                                    break Attempt::Abandoned;
+                                }
+                                _ => return Err(session
+                                    .disconnect(
+                                        DisconnectReason::ProtocolError,
+                                        "Unexpected response to the `keyboard-interactive` prompts",
+                                    )
+                                    .await
+                                    .into()),
+                            }
+                        }
+                        keyboard_interactive::Response::Reject => break Attempt::Failure,
+                    }
+                }
             }
         })
     }
 }
 
-impl<H: Handler, N: none::None, P: password::Password, PK: publickey::Publickey> Handler
-    for Auth<H, N, P, PK>
+impl<H, N, P, PK, KI, HB, RM, ID> Handler for Auth<H, N, P, PK, KI, HB, RM, ID>
+where
+    H: Handler,
+    N: none::None,
+    P: password::Password,
+    PK: publickey::Publickey,
+    KI: keyboard_interactive::KeyboardInteractive,
+    HB: hostbased::Hostbased,
+    RM: required::Required,
+    ID: identity::Identify,
 {
     type Err = H::Err;
-    type Ok<'s, IO: 's, S: 's> = H::Ok<'s, IO, S>;
+    type Ok<'s, IO: 's, S: 's> = (identity::Identity, H::Ok<'s, IO, S>);
 
     const SERVICE_NAME: &'static str = crate::SERVICE_NAME;
 
@@ -281,76 +860,12 @@ impl<H: Handler, N: none::None, P: password::Password, PK: publickey::Publickey>
         IO: AsyncBufRead + AsyncWrite + Unpin,
         S: Side,
     {
-        if let Some(message) = self.banner.take() {
-            session
-                .send(&userauth::Banner {
-                    message,
-                    ..Default::default()
-                })
-                .await?;
-        }
+        self.state.deadline = self
+            .grace_time
+            .map(|grace_time| Instant::now() + grace_time);
 
-        loop {
-            if let Ok(userauth::Request {
-                username,
-                service_name,
-                method,
-            }) = session.recv().await?.to()
-            {
-                if self.methods.remove(*method.as_ref()) {
-                    match self
-                        .handle_attempt(session, username, method, &service_name)
-                        .await?
-                    {
-                        Attempt::Success => {
-                            break if &*service_name == H::SERVICE_NAME {
-                                session.send(&userauth::Success).await?;
+        let identity = self.authenticate(session).await?;
 
-                                self.handler.on_request(session).await
-                            } else {
-                                Err(Error::from(
-                                    session
-                                        .disconnect(
-                                            DisconnectReason::ServiceNotAvailable,
-                                            "Requested service is unknown",
-                                        )
-                                        .await,
-                                )
-                                .into())
-                            }
-                        }
-                        attempt @ Attempt::Failure | attempt @ Attempt::Partial => {
-                            session
-                                .send(&userauth::Failure {
-                                    continue_with: NameList::new(self.methods),
-                                    partial_success: (attempt == Attempt::Partial).into(),
-                                })
-                                .await?;
-                        }
-                        Attempt::Continue => (),
-                    }
-                } else {
-                    session
-                        .send(&userauth::Failure {
-                            continue_with: NameList::new(self.methods),
-                            partial_success: false.into(),
-                        })
-                        .await?;
-                }
-            } else {
-                break Err(Error::from(
-                    session
-                        .disconnect(
-                            DisconnectReason::ProtocolError,
-                            format!(
-                                "Unexpected message in the context of the `{}` service request",
-                                Self::SERVICE_NAME
-                            ),
-                        )
-                        .await,
-                )
-                .into());
-            }
-        }
+        Ok((identity, self.handler.on_request(session).await?))
     }
 }
@@ -1,5 +1,7 @@
 //! The `none` authentication method.
 
+use futures::Future;
+
 /// The response to the authentication request.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Response {
@@ -10,21 +12,29 @@ pub enum Response {
     Reject,
 }
 
+impl_into_future!(Response);
+
 /// An interface to the `none` authentication method.
 pub trait None: Send + Sync {
     /// Process the authentication request.
-    fn process(&mut self, user: String) -> Response;
+    fn process(&mut self, user: String) -> impl Future<Output = Response> + Send;
 }
 
-impl<T: FnMut(String) -> Response + Send + Sync> None for T {
-    fn process(&mut self, user: String) -> Response {
-        (self)(user)
+/// An implementation for closures.
+impl<T, F> None for T
+where
+    T: FnMut(String) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Response>,
+    F::IntoFuture: Send,
+{
+    fn process(&mut self, user: String) -> impl Future<Output = Response> + Send {
+        (self)(user).into_future()
     }
 }
 
 /// A default implementation of the method that rejects all requests.
 impl None for () {
-    fn process(&mut self, _: String) -> Response {
+    async fn process(&mut self, _: String) -> Response {
         Response::Reject
     }
 }
@@ -1,5 +1,7 @@
 //! The `password` authentication method.
 
+use futures::Future;
+
 /// The response to the authentication request.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Response {
@@ -16,21 +18,39 @@ pub enum Response {
     Reject,
 }
 
+impl_into_future!(Response);
+
 /// An interface to the `password` authentication method.
 pub trait Password: Send + Sync {
     /// Process the authentication request.
-    fn process(&mut self, user: String, password: String, newpassword: Option<String>) -> Response;
+    fn process(
+        &mut self,
+        user: String,
+        password: String,
+        newpassword: Option<String>,
+    ) -> impl Future<Output = Response> + Send;
 }
 
-impl<T: FnMut(String, String, Option<String>) -> Response + Send + Sync> Password for T {
-    fn process(&mut self, user: String, password: String, newpassword: Option<String>) -> Response {
-        (self)(user, password, newpassword)
+/// An implementation for closures.
+impl<T, F> Password for T
+where
+    T: FnMut(String, String, Option<String>) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Response>,
+    F::IntoFuture: Send,
+{
+    fn process(
+        &mut self,
+        user: String,
+        password: String,
+        newpassword: Option<String>,
+    ) -> impl Future<Output = Response> + Send {
+        (self)(user, password, newpassword).into_future()
     }
 }
 
 /// A default implementation of the method that rejects all requests.
 impl Password for () {
-    fn process(&mut self, _: String, _: String, _: Option<String>) -> Response {
+    async fn process(&mut self, _: String, _: String, _: Option<String>) -> Response {
         Response::Reject
     }
 }
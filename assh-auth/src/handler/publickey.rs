@@ -1,5 +1,7 @@
 //! The `publickey` authentication method.
 
+use futures::Future;
+
 #[doc(no_inline)]
 pub use ssh_key::PublicKey;
 
@@ -13,21 +15,29 @@ pub enum Response {
     Reject,
 }
 
+impl_into_future!(Response);
+
 /// An interface to the `publickey` authentication method.
 pub trait Publickey: Send + Sync {
     /// Process the authentication request.
-    fn process(&mut self, user: String, key: PublicKey) -> Response;
+    fn process(&mut self, user: String, key: PublicKey) -> impl Future<Output = Response> + Send;
 }
 
-impl<T: FnMut(String, PublicKey) -> Response + Send + Sync> Publickey for T {
-    fn process(&mut self, user: String, key: PublicKey) -> Response {
-        (self)(user, key)
+/// An implementation for closures.
+impl<T, F> Publickey for T
+where
+    T: FnMut(String, PublicKey) -> F + Send + Sync,
+    F: std::future::IntoFuture<Output = Response>,
+    F::IntoFuture: Send,
+{
+    fn process(&mut self, user: String, key: PublicKey) -> impl Future<Output = Response> + Send {
+        (self)(user, key).into_future()
     }
 }
 
 /// A default implementation of the method that rejects all requests.
 impl Publickey for () {
-    fn process(&mut self, _: String, _: PublicKey) -> Response {
+    async fn process(&mut self, _: String, _: PublicKey) -> Response {
         Response::Reject
     }
 }
@@ -0,0 +1,25 @@
+//! The chains of authentication methods _required_ to authenticate,
+//! akin to OpenSSH's `AuthenticationMethods`.
+
+use super::Method;
+
+/// An interface to the methods required for a user to authenticate.
+pub trait Required: Send + Sync {
+    /// List the chains of methods the `user` has to complete, in order, to authenticate;
+    /// completing any of the chains authenticates the user and empty chains are ignored,
+    /// while an empty list lets any single method authenticate the user.
+    fn chains(&mut self, user: String) -> Vec<Vec<Method>>;
+}
+
+impl<T: FnMut(String) -> Vec<Vec<Method>> + Send + Sync> Required for T {
+    fn chains(&mut self, user: String) -> Vec<Vec<Method>> {
+        (self)(user)
+    }
+}
+
+/// A default implementation that lets any single method authenticate the user.
+impl Required for () {
+    fn chains(&mut self, _: String) -> Vec<Vec<Method>> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Instant;
+
+use enumset::EnumSet;
+use ssh_key::PublicKey;
+use ssh_packet::userauth;
+
+use super::{identity::Identity, Attempt, Method};
+
+/// The authentication state of the session.
+#[derive(Debug, Default)]
+pub struct State {
+    /// The user currently attempting to authenticate.
+    pub user: Option<User>,
+
+    /// The count of failed attempts, across all users.
+    pub attempts: usize,
+
+    /// A request received while handling the previous one, to be processed next.
+    pub pending: Option<userauth::Request>,
+
+    /// The instant after which the session is disconnected, if a grace time is set.
+    pub deadline: Option<Instant>,
+}
+
+/// The authentication state of a single user.
+#[derive(Debug)]
+pub struct User {
+    pub name: String,
+
+    /// The methods enabled for the user.
+    pub enabled: EnumSet<Method>,
+
+    /// The methods the user is still able to attempt.
+    pub methods: EnumSet<Method>,
+
+    /// The remaining methods of the chains required to authenticate.
+    pub chains: Vec<Vec<Method>>,
+
+    /// The methods successfully completed by the user, in order.
+    pub completed: Vec<Method>,
+
+    /// The last key the user authenticated with.
+    pub key: Option<PublicKey>,
+}
+
+impl User {
+    pub fn new(name: String, methods: EnumSet<Method>, mut chains: Vec<Vec<Method>>) -> Self {
+        chains.retain(|chain| !chain.is_empty());
+
+        Self {
+            name,
+            enabled: methods,
+            methods,
+            chains,
+            completed: Vec::new(),
+            key: None,
+        }
+    }
+
+    /// The methods that can be attempted, restricted to the next ones in the required chains.
+    pub fn allowed(&self) -> EnumSet<Method> {
+        if self.chains.is_empty() {
+            self.methods
+        } else {
+            self.methods & self.heads()
+        }
+    }
+
+    /// The next methods of the required chains.
+    fn heads(&self) -> EnumSet<Method> {
+        self.chains
+            .iter()
+            .filter_map(|chain| chain.first())
+            .collect()
+    }
+
+    /// Turn the state into the [`Identity`] of the authenticated user.
+    pub fn into_identity(self) -> Identity {
+        Identity {
+            username: self.name,
+            methods: self.completed,
+            key: self.key,
+            context: Default::default(),
+        }
+    }
+
+    /// Advance the required chains after a successful `method`,
+    /// the authentication only succeeds once one of the chains is exhausted.
+    pub fn advance(&mut self, method: Method) -> Attempt {
+        self.completed.push(method);
+
+        if self.chains.is_empty() {
+            return Attempt::Success;
+        }
+
+        for chain in self.chains.iter_mut() {
+            if chain.first() == Some(&method) {
+                chain.remove(0);
+            }
+        }
+
+        if self.chains.iter().any(Vec::is_empty) {
+            Attempt::Success
+        } else {
+            // Allow the next methods of every chain, including the ones already attempted.
+            self.methods = self.enabled & self.heads();
+
+            Attempt::Partial
+        }
+    }
+}
@@ -22,5 +22,7 @@
 
 const SERVICE_NAME: &str = "ssh-userauth";
 
+pub mod cryptography;
+
 pub mod handler;
 pub mod request;
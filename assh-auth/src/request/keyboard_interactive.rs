@@ -0,0 +1,28 @@
+//! The `keyboard-interactive` authentication method.
+
+#[doc(no_inline)]
+pub use crate::handler::keyboard_interactive::Prompt;
+
+/// An interface to answer the prompts of the `keyboard-interactive` authentication method.
+pub trait KeyboardInteractive: Send + Sync {
+    /// Answer the `prompts` of a challenge from the server, in order,
+    /// the challenge may contain no prompts, only to display its `name` and `instruction`.
+    ///
+    /// # Note
+    /// Exactly one answer per prompt must be returned, otherwise the authentication fails
+    /// locally with an [`std::io::ErrorKind::InvalidInput`] error, before anything is sent.
+    fn respond(&mut self, name: String, instruction: String, prompts: Vec<Prompt>) -> Vec<String>;
+}
+
+impl<T: FnMut(String, String, Vec<Prompt>) -> Vec<String> + Send + Sync> KeyboardInteractive for T {
+    fn respond(&mut self, name: String, instruction: String, prompts: Vec<Prompt>) -> Vec<String> {
+        (self)(name, instruction, prompts)
+    }
+}
+
+/// A default implementation of the method that answers all prompts with an empty string.
+impl KeyboardInteractive for () {
+    fn respond(&mut self, _: String, _: String, prompts: Vec<Prompt>) -> Vec<String> {
+        vec![String::new(); prompts.len()]
+    }
+}
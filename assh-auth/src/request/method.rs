@@ -12,6 +12,16 @@ pub enum Method {
 
     /// The SSH `password` authentication method.
     Password { password: String },
+
+    /// The SSH `hostbased` authentication method.
+    Hostbased {
+        key: Box<PrivateKey>,
+        client_fqdn: String,
+        client_username: String,
+    },
+
+    /// The SSH `keyboard-interactive` authentication method.
+    KeyboardInteractive,
 }
 
 impl std::hash::Hash for Method {
@@ -19,7 +29,7 @@ impl std::hash::Hash for Method {
         core::mem::discriminant(self).hash(state);
 
         // Allow keys with different fingerprints to exist alongside
-        if let Self::Publickey { key } = self {
+        if let Self::Publickey { key } | Self::Hostbased { key, .. } = self {
             key.fingerprint(ssh_key::HashAlg::Sha256)
                 .as_bytes()
                 .hash(state);
@@ -33,6 +43,8 @@ impl AsRef<str> for Method {
             Self::None { .. } => userauth::Method::NONE,
             Self::Publickey { .. } => userauth::Method::PUBLICKEY,
             Self::Password { .. } => userauth::Method::PASSWORD,
+            Self::Hostbased { .. } => userauth::Method::HOSTBASED,
+            Self::KeyboardInteractive => userauth::Method::KEYBOARD_INTERACTIVE,
         }
     }
 }
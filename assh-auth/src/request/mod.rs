@@ -2,8 +2,14 @@
 
 use hashbrown::HashSet;
 
-use assh::{service::Request, side::Side, Error, Result, Session};
+use assh::{
+    algorithm::{key, Key},
+    service::Request,
+    side::Side,
+    Error, Result, Session,
+};
 use futures::{AsyncBufRead, AsyncWrite};
+use ssh_key::HashAlg;
 use ssh_packet::{
     arch::{self, StringUtf8},
     cryptography::PublickeySignature,
@@ -14,8 +20,11 @@ use ssh_packet::{
 mod method;
 use method::Method;
 
-// TODO: Add hostbased authentication.
-// TODO: Add keyboard-interactive authentication.
+pub mod keyboard_interactive;
+pub mod password;
+
+use crate::cryptography::HostbasedSignature;
+
 // TODO: Handle the SSH banner in the `request` side.
 
 #[doc(no_inline)]
@@ -23,11 +32,14 @@ pub use ssh_key::PrivateKey;
 
 /// The authentication service [`Request`] for sessions.
 #[derive(Debug)]
-pub struct Auth<R> {
+pub struct Auth<R, KI = (), PC = ()> {
     username: StringUtf8,
     service: R,
 
     methods: HashSet<Method>,
+
+    keyboard_interactive: KI,
+    password_change: PC,
 }
 
 impl<R: Request> Auth<R> {
@@ -35,18 +47,28 @@ impl<R: Request> Auth<R> {
     ///
     /// # Note
     /// 1. The layer always starts with the `none` authentication method
-    /// to discover the methods available on the server.
+    ///    to discover the methods available on the server.
     /// 2. While the `publickey` method allows for multiple keys,
-    /// the `password` method will only keep the last one provided to [`Self::password`].
+    ///    the `password` method will only keep the last one provided to [`Self::password`].
     pub fn new(username: impl Into<StringUtf8>, service: R) -> Self {
         Self {
             username: username.into(),
             service,
 
             methods: Default::default(),
+
+            keyboard_interactive: (),
+            password_change: (),
         }
     }
+}
 
+impl<R, KI, PC> Auth<R, KI, PC>
+where
+    R: Request,
+    KI: keyboard_interactive::KeyboardInteractive,
+    PC: password::PasswordChange,
+{
     /// Attempt to authenticate with the `password` method.
     pub fn password(mut self, password: impl Into<String>) -> Self {
         self.methods.replace(Method::Password {
@@ -65,6 +87,71 @@ impl<R: Request> Auth<R> {
         self
     }
 
+    /// Attempt to authenticate with the `hostbased` method, on behalf of the `client_username`
+    /// on the host named `client_fqdn`, which possesses the private `host_key`.
+    pub fn hostbased(
+        mut self,
+        host_key: impl Into<PrivateKey>,
+        client_fqdn: impl Into<String>,
+        client_username: impl Into<String>,
+    ) -> Self {
+        self.methods.replace(Method::Hostbased {
+            key: host_key.into().into(),
+            client_fqdn: client_fqdn.into(),
+            client_username: client_username.into(),
+        });
+
+        self
+    }
+
+    /// Attempt to authenticate with the `keyboard-interactive` method,
+    /// answering the prompts of the server with the provided callback.
+    pub fn keyboard_interactive(
+        self,
+        keyboard_interactive: impl keyboard_interactive::KeyboardInteractive,
+    ) -> Auth<R, impl keyboard_interactive::KeyboardInteractive, PC> {
+        let Self {
+            username,
+            service,
+            mut methods,
+            keyboard_interactive: _,
+            password_change,
+        } = self;
+
+        methods.replace(Method::KeyboardInteractive);
+
+        Auth {
+            username,
+            service,
+            methods,
+            keyboard_interactive,
+            password_change,
+        }
+    }
+
+    /// Answer the password change requests of the server with the provided callback,
+    /// when attempting to authenticate with the `password` method.
+    pub fn password_change(
+        self,
+        password_change: impl password::PasswordChange,
+    ) -> Auth<R, KI, impl password::PasswordChange> {
+        let Self {
+            username,
+            service,
+            methods,
+            keyboard_interactive,
+            password_change: _,
+        } = self;
+
+        Auth {
+            username,
+            service,
+            methods,
+            keyboard_interactive,
+            password_change,
+        }
+    }
+
     fn next_method(&mut self, continue_with: &arch::NameList) -> Option<Method> {
         self.methods
             .extract_if(|m| continue_with.into_iter().any(|method| m.as_ref() == method))
@@ -75,7 +162,7 @@ impl<R: Request> Auth<R> {
         &mut self,
         session: &mut Session<IO, S>,
         method: &Method,
-    ) -> Result<Packet> {
+    ) -> Result<Option<Packet>> {
         let build = |method| userauth::Request {
             username: self.username.clone(),
             service_name: R::SERVICE_NAME.into(),
@@ -86,7 +173,7 @@ impl<R: Request> Auth<R> {
             Method::None => {
                 session.send(&build(userauth::Method::None)).await?;
 
-                session.recv().await
+                session.recv().await.map(Some)
             }
             Method::Publickey { key } => {
                 // Probe the server to know if this algorithm is implemented.
@@ -120,9 +207,9 @@ impl<R: Request> Auth<R> {
                         }))
                         .await?;
 
-                    session.recv().await
+                    session.recv().await.map(Some)
                 } else {
-                    Ok(response)
+                    Ok(Some(response))
                 }
             }
             Method::Password { password } => {
@@ -133,18 +220,133 @@ impl<R: Request> Auth<R> {
                     }))
                     .await?;
 
-                let response = session.recv().await?;
-                if let Ok(userauth::PasswdChangereq { prompt: _, .. }) = response.to() {
-                    todo!() // TODO: Handle the change request case
-                } else {
-                    Ok(response)
+                let mut response = session.recv().await?;
+
+                // The server may ask again for a password change if the new password is unacceptable.
+                while let Ok(userauth::PasswdChangereq { prompt, .. }) = response.to() {
+                    let Some((password, new)) = self.password_change.change(prompt.into_string())
+                    else {
+                        return Ok(None);
+                    };
+
+                    session
+                        .send(&build(userauth::Method::Password {
+                            password: password.into(),
+                            new: Some(new.into()),
+                        }))
+                        .await?;
+
+                    response = session.recv().await?;
+                }
+
+                Ok(Some(response))
+            }
+            Method::Hostbased {
+                key: host_key,
+                client_fqdn,
+                client_username,
+            } => {
+                // RSA keys sign with `rsa-sha2-512` rather than `ssh-rsa`,
+                // since signatures over SHA-1 are refused by most servers.
+                let keyalg = match host_key.algorithm() {
+                    Key::Rsa { .. } => Key::Rsa {
+                        hash: Some(HashAlg::Sha512),
+                    },
+                    keyalg => keyalg,
+                };
+
+                let algorithm = keyalg.as_str().into();
+                let blob = host_key.public_key().to_bytes()?.into();
+                let client_fqdn = client_fqdn.as_str().into();
+                let client_username = client_username.as_str().into();
+
+                let message = HostbasedSignature {
+                    session_id: &session.session_id().unwrap_or_default().into(),
+                    username: &self.username,
+                    service_name: &R::SERVICE_NAME.into(),
+                    algorithm: &algorithm,
+                    host_key: &blob,
+                    client_fqdn: &client_fqdn,
+                    client_username: &client_username,
+                }
+                .to_bytes()?;
+                let signature = key::sign(host_key, &keyalg, &message)?;
+
+                session
+                    .send(&build(userauth::Method::Hostbased {
+                        algorithm,
+                        host_key: blob,
+                        client_fqdn,
+                        username: client_username,
+                        signature: signature.into(),
+                    }))
+                    .await?;
+
+                session.recv().await.map(Some)
+            }
+            Method::KeyboardInteractive => {
+                session
+                    .send(&build(userauth::Method::KeyboardInteractive {
+                        language: Default::default(),
+                        submethods: Default::default(),
+                    }))
+                    .await?;
+
+                loop {
+                    let response = session.recv().await?;
+                    if let Ok(userauth::InfoRequest {
+                        name,
+                        instruction,
+                        prompts,
+                        ..
+                    }) = response.to()
+                    {
+                        let count = prompts.len();
+                        let responses = self.keyboard_interactive.respond(
+                            name.into_string(),
+                            instruction.into_string(),
+                            prompts
+                                .into_iter()
+                                .map(|prompt| {
+                                    keyboard_interactive::Prompt::new(
+                                        prompt.prompt.into_string(),
+                                        *prompt.echo,
+                                    )
+                                })
+                                .collect(),
+                        );
+
+                        if responses.len() != count {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidInput,
+                                format!(
+                                    "Answered {} of the {count} `keyboard-interactive` prompts",
+                                    responses.len()
+                                ),
+                            )
+                            .into());
+                        }
+
+                        session
+                            .send(&userauth::InfoResponse {
+                                responses: responses.into_iter().map(Into::into).collect(),
+                            })
+                            .await?;
+                    } else {
+                        break Ok(Some(response));
+                    }
                 }
             }
         }
     }
 }
 
-impl<R: Request> Request for Auth<R> {
+impl<R, KI, PC> Request for Auth<R, KI, PC>
+where
+    R: Request,
+    KI: keyboard_interactive::KeyboardInteractive,
+    PC: password::PasswordChange,
+{
     type Err = R::Err;
     type Ok<'s, IO: 's, S: 's> = R::Ok<'s, IO, S>;
 
@@ -159,42 +361,56 @@ impl<R: Request> Request for Auth<R> {
         S: Side,
     {
         let mut method = Method::None;
+        let mut continue_with = arch::NameList::default();
 
         loop {
-            let response = self.attempt_method(session, &method).await?;
-
-            if response.to::<userauth::Success>().is_ok() {
-                break self.service.on_accept(session).await;
-            } else if let Ok(userauth::Failure { continue_with, .. }) = response.to() {
-                // TODO: Take care of partial success
+            // An abandoned method continues with the methods of the last failure.
+            if let Some(response) = self.attempt_method(session, &method).await? {
+                if response.to::<userauth::Success>().is_ok() {
+                    break self.service.on_accept(session).await;
+                } else if let Ok(userauth::Failure {
+                    continue_with: methods,
+                    partial_success,
+                }) = response.to()
+                {
+                    // On partial success, the server lists the methods of the next required factor.
+                    if *partial_success {
+                        tracing::debug!(
+                            "Partial success using method `{}`, continuing with {methods:?}",
+                            method.as_ref()
+                        );
+                    }
 
-                if let Some(next) = self.next_method(&continue_with) {
-                    method = next;
+                    continue_with = methods;
                 } else {
                     break Err(Error::from(
                         session
                             .disconnect(
-                                DisconnectReason::NoMoreAuthMethodsAvailable,
-                                "Exhausted available authentication methods",
+                                DisconnectReason::ProtocolError,
+                                format!(
+                                    "Unexpected message in the context of the `{}` service request",
+                                    Self::SERVICE_NAME
+                                ),
                             )
                             .await,
                     )
                     .into());
-                };
+                }
+            }
+
+            if let Some(next) = self.next_method(&continue_with) {
+                method = next;
             } else {
                 break Err(Error::from(
                     session
                         .disconnect(
-                            DisconnectReason::ProtocolError,
-                            format!(
-                                "Unexpected message in the context of the `{}` service request",
-                                Self::SERVICE_NAME
-                            ),
+                            DisconnectReason::NoMoreAuthMethodsAvailable,
+                            "Exhausted available authentication methods",
                         )
                         .await,
                 )
                 .into());
-            }
+            };
         }
     }
 }
@@ -0,0 +1,21 @@
+//! The `password` authentication method.
+
+/// An interface to answer the password change requests of the `password` authentication method.
+pub trait PasswordChange: Send + Sync {
+    /// Answer the password change request displaying the `prompt` with the _old_ and _new_ passwords,
+    /// or [`None`] to abandon the `password` method.
+    fn change(&mut self, prompt: String) -> Option<(String, String)>;
+}
+
+impl<T: FnMut(String) -> Option<(String, String)> + Send + Sync> PasswordChange for T {
+    fn change(&mut self, prompt: String) -> Option<(String, String)> {
+        (self)(prompt)
+    }
+}
+
+/// A default implementation of the method that abandons all password change requests.
+impl PasswordChange for () {
+    fn change(&mut self, _: String) -> Option<(String, String)> {
+        None
+    }
+}
@@ -3,42 +3,312 @@ use assh::{
     Result,
 };
 use assh_auth::{handler, request};
-use async_compat::CompatExt;
-use tokio::io::BufStream;
 
 mod cookie;
 
+type Session<S> = assh::Session<assh::io::Tokio<tokio::io::DuplexStream>, S>;
+
+/// Establish a pair of connected sessions, the server using a random `ssh-ed25519` host key.
+async fn pair() -> Result<(Session<Server>, Session<Client>)> {
+    let duplex = tokio::io::duplex(ssh_packet::PACKET_MAX_SIZE * 16);
+
+    let server = Server {
+        keys: vec![ssh_key::private::PrivateKey::random(
+            &mut rand::thread_rng(),
+            ssh_key::Algorithm::Ed25519,
+        )?],
+        ..Default::default()
+    };
+
+    tokio::try_join!(
+        assh::Session::new_tokio(duplex.0, server),
+        assh::Session::new_tokio(duplex.1, Client::default()),
+    )
+}
+
 #[tokio::test]
 async fn basic_none() -> Result<(), Box<dyn std::error::Error>> {
-    let duplex = tokio::io::duplex(ssh_packet::PACKET_MAX_SIZE * 16);
+    let (mut server, mut client) = pair().await?;
+
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie0.clone()).none(|_| handler::none::Response::Accept),
+                )
+                .await
+        },
+        async {
+            client
+                .request(request::Auth::new("user", cookie1.clone()))
+                .await
+        },
+    )?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn basic_keyboard_interactive() -> Result<(), Box<dyn std::error::Error>> {
+    use handler::keyboard_interactive::{Prompt, Response};
+
+    let (mut server, mut client) = pair().await?;
 
     let cookie0 = cookie::Cookie::default();
     let cookie1 = cookie::Cookie::default();
 
     tokio::try_join!(
         async {
-            let server = Server {
-                keys: vec![ssh_key::private::PrivateKey::random(
-                    &mut rand::thread_rng(),
-                    ssh_key::Algorithm::Ed25519,
+            server
+                .handle(handler::Auth::new(cookie0.clone()).keyboard_interactive(
+                    |_, responses: Option<Vec<String>>| match responses.as_deref() {
+                        None => Response::Challenge {
+                            name: "Password".into(),
+                            instruction: Default::default(),
+                            prompts: vec![Prompt::new("Password: ", false)],
+                        },
+                        Some([password]) if password == "password" => Response::Challenge {
+                            name: "Second factor".into(),
+                            instruction: "Enter the code from your device".into(),
+                            prompts: vec![Prompt::new("Code: ", true)],
+                        },
+                        Some([code]) if code == "123456" => Response::Challenge {
+                            name: "Welcome".into(),
+                            instruction: "Access granted".into(),
+                            prompts: vec![],
+                        },
+                        Some([]) => Response::Accept,
+                        _ => Response::Reject,
+                    },
+                ))
+                .await
+        },
+        async {
+            client
+                .request(
+                    request::Auth::new("user", cookie1.clone()).keyboard_interactive(
+                        |_, _, prompts: Vec<request::keyboard_interactive::Prompt>| {
+                            prompts
+                                .iter()
+                                .map(|prompt| match prompt.prompt.as_str() {
+                                    "Password: " => "password".into(),
+                                    "Code: " => "123456".into(),
+                                    _ => String::new(),
+                                })
+                                .collect()
+                        },
+                    ),
                 )
-                .unwrap()],
-                ..Default::default()
-            };
-            let mut server = assh::Session::new(BufStream::new(duplex.0).compat(), server).await?;
+                .await
+        },
+    )?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn keyboard_interactive_mismatched_responses() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
 
+    let (_, client) = tokio::join!(
+        async {
             server
                 .handle(
-                    handler::Auth::new(cookie0.clone()).none(|_| handler::none::Response::Accept),
+                    handler::Auth::new(cookie::Cookie::default()).keyboard_interactive(|_, _| {
+                        handler::keyboard_interactive::Response::Challenge {
+                            name: Default::default(),
+                            instruction: Default::default(),
+                            prompts: vec![handler::keyboard_interactive::Prompt::new(
+                                "Code: ", true,
+                            )],
+                        }
+                    }),
+                )
+                .await
+                .map(drop)
+        },
+        // The client session is dropped on failure, to end the server's exchange.
+        async move {
+            client
+                .request(
+                    request::Auth::new("user", cookie::Cookie::default())
+                        .keyboard_interactive(|_, _, _| vec!["123456".into(), "extra".into()]),
+                )
+                .await
+                .map(drop)
+        },
+    );
+
+    let Err(assh::Error::Io(err)) = client else {
+        panic!("Expected the request to fail locally");
+    };
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn basic_hostbased() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let host_key =
+        ssh_key::PrivateKey::random(&mut rand::thread_rng(), ssh_key::Algorithm::Ed25519)?;
+    let trusted = host_key.public_key().clone();
+
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(handler::Auth::new(cookie0.clone()).hostbased(
+                    move |user: String, key, fqdn: String, client_user: String| {
+                        if user == "user"
+                            && key == trusted
+                            && fqdn == "node.cluster.local"
+                            && client_user == "scheduler"
+                        {
+                            handler::hostbased::Response::Accept
+                        } else {
+                            handler::hostbased::Response::Reject
+                        }
+                    },
+                ))
+                .await
+        },
+        async {
+            client
+                .request(request::Auth::new("user", cookie1.clone()).hostbased(
+                    host_key,
+                    "node.cluster.local",
+                    "scheduler",
+                ))
+                .await
+        },
+    )?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rsa_hostbased() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let host_key = ssh_key::PrivateKey::from_openssh(include_str!("../../assh/tests/common/rsa"))?;
+    let trusted = host_key.public_key().clone();
+
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie0.clone()).hostbased(move |_, key, _, _| {
+                        if key == trusted {
+                            handler::hostbased::Response::Accept
+                        } else {
+                            handler::hostbased::Response::Reject
+                        }
+                    }),
                 )
                 .await
         },
         async {
-            let client = Client::default();
-            let mut client = assh::Session::new(BufStream::new(duplex.1).compat(), client).await?;
+            client
+                .request(request::Auth::new("user", cookie1.clone()).hostbased(
+                    host_key,
+                    "node.cluster.local",
+                    "scheduler",
+                ))
+                .await
+        },
+    )?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
 
+    Ok(())
+}
+
+#[tokio::test]
+async fn password_change() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(handler::Auth::new(cookie0.clone()).password(
+                    |_, password: String, new: Option<String>| match (
+                        password.as_str(),
+                        new.as_deref(),
+                    ) {
+                        ("expired", None) => handler::password::Response::PasswordExpired {
+                            prompt: "Your password has expired".into(),
+                        },
+                        ("expired", Some("weak")) => handler::password::Response::PasswordExpired {
+                            prompt: "Your new password is too weak".into(),
+                        },
+                        ("expired", Some("str0ng")) => handler::password::Response::Accept,
+                        _ => handler::password::Response::Reject,
+                    },
+                ))
+                .await
+        },
+        async {
             client
-                .request(request::Auth::new("user", cookie1.clone()))
+                .request(
+                    request::Auth::new("user", cookie1.clone())
+                        .password("expired")
+                        .password_change(|prompt: String| {
+                            let new = if prompt.contains("weak") {
+                                "str0ng"
+                            } else {
+                                "weak"
+                            };
+
+                            Some(("expired".into(), new.into()))
+                        }),
+                )
                 .await
         },
     )?;
@@ -54,3 +324,509 @@ async fn basic_none() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+async fn chained(
+    request: impl assh::service::Request<Err = assh::Error>,
+    cookie: cookie::Cookie,
+) -> Result<()> {
+    let (mut server, mut client) = pair().await?;
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie)
+                        .password(|_, password: String, _| {
+                            if password == "password" {
+                                handler::password::Response::Accept
+                            } else {
+                                handler::password::Response::Reject
+                            }
+                        })
+                        .keyboard_interactive(|_, responses: Option<Vec<String>>| {
+                            match responses.as_deref() {
+                                None => handler::keyboard_interactive::Response::Challenge {
+                                    name: Default::default(),
+                                    instruction: Default::default(),
+                                    prompts: vec![handler::keyboard_interactive::Prompt::new(
+                                        "Code: ", true,
+                                    )],
+                                },
+                                Some([code]) if code == "123456" => {
+                                    handler::keyboard_interactive::Response::Accept
+                                }
+                                _ => handler::keyboard_interactive::Response::Reject,
+                            }
+                        })
+                        .required(|_| {
+                            vec![vec![
+                                handler::Method::Password,
+                                handler::Method::KeyboardInteractive,
+                            ]]
+                        }),
+                )
+                .await
+        },
+        async { client.request(request).await.map(drop) },
+    )?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn required_chain() -> Result<(), Box<dyn std::error::Error>> {
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    chained(
+        request::Auth::new("user", cookie1.clone())
+            .password("password")
+            .keyboard_interactive(
+                |_, _, prompts: Vec<request::keyboard_interactive::Prompt>| {
+                    prompts.iter().map(|_| "123456".into()).collect()
+                },
+            ),
+        cookie0.clone(),
+    )
+    .await?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn required_chain_incomplete() -> Result<(), Box<dyn std::error::Error>> {
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    assert!(chained(
+        request::Auth::new("user", cookie1.clone()).password("password"),
+        cookie0.clone(),
+    )
+    .await
+    .is_err());
+
+    assert!(
+        !cookie0.is_flagged(),
+        "Authentication handling succeeded with a single factor"
+    );
+    assert!(
+        !cookie1.is_flagged(),
+        "Authentication request succeeded with a single factor"
+    );
+
+    Ok(())
+}
+
+/// A bare `password` client, sending the scripted `(username, password)` attempts in order,
+/// a missing password being a `none` attempt, and collecting the responses of the server.
+struct Scripted(Vec<(&'static str, Option<&'static str>)>);
+
+impl assh::service::Request for Scripted {
+    const SERVICE_NAME: &'static str = "ssh-userauth";
+
+    type Err = assh::Error;
+    type Ok<'s, IO: 's, S: 's> = Vec<&'static str>;
+
+    async fn on_accept<'s, IO, S>(
+        &mut self,
+        session: &'s mut assh::Session<IO, S>,
+    ) -> Result<Self::Ok<'s, IO, S>, Self::Err>
+    where
+        IO: futures::AsyncBufRead + futures::AsyncWrite + Unpin,
+        S: assh::side::Side,
+    {
+        use ssh_packet::userauth;
+
+        let mut responses = Vec::new();
+
+        for (username, password) in &self.0 {
+            session
+                .send(&userauth::Request {
+                    username: (*username).into(),
+                    service_name: <cookie::Cookie as assh::service::Request>::SERVICE_NAME.into(),
+                    method: match password {
+                        Some(password) => userauth::Method::Password {
+                            password: (*password).into(),
+                            new: None,
+                        },
+                        None => userauth::Method::None,
+                    },
+                })
+                .await?;
+
+            let packet = session.recv().await?;
+            responses.push(match packet.to::<userauth::Failure>() {
+                Ok(userauth::Failure {
+                    partial_success, ..
+                }) if *partial_success => "partial",
+                Ok(_) => "failure",
+                Err(_) => {
+                    packet.to::<userauth::Success>()?;
+
+                    "success"
+                }
+            });
+        }
+
+        Ok(responses)
+    }
+}
+
+async fn scripted(
+    configure: impl FnOnce(handler::Auth<cookie::Cookie>) -> handler::Auth<cookie::Cookie>,
+    script: Scripted,
+) -> Result<(Result<()>, Result<Vec<&'static str>>)> {
+    let (mut server, mut client) = pair().await?;
+
+    Ok(tokio::join!(
+        async {
+            server
+                .handle(
+                    configure(handler::Auth::new(cookie::Cookie::default()))
+                        .password(|_, password: String, _| {
+                            if password == "first" || password == "second" {
+                                handler::password::Response::Accept
+                            } else {
+                                handler::password::Response::Reject
+                            }
+                        })
+                        .required(|_| {
+                            vec![vec![handler::Method::Password, handler::Method::Password]]
+                        }),
+                )
+                .await
+                .map(drop)
+        },
+        async { client.request(script).await },
+    ))
+}
+
+#[tokio::test]
+async fn user_switch_resets_state() -> Result<(), Box<dyn std::error::Error>> {
+    let (_, client) = scripted(
+        |auth| auth,
+        Scripted(vec![
+            ("alice", Some("first")),
+            ("bob", Some("second")),
+            ("bob", Some("second")),
+        ]),
+    )
+    .await?;
+
+    assert_eq!(client?, ["partial", "partial", "success"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_attempts_disconnects() -> Result<(), Box<dyn std::error::Error>> {
+    let (server, client) = scripted(
+        |auth| auth.max_attempts(2),
+        Scripted(vec![
+            ("alice", Some("wrong")),
+            ("bob", Some("wrong")),
+            ("bob", Some("first")),
+        ]),
+    )
+    .await?;
+
+    assert!(matches!(
+        server.unwrap_err().disconnect_reason(),
+        Some(ssh_packet::trans::DisconnectReason::NoMoreAuthMethodsAvailable)
+    ));
+    assert!(matches!(
+        client.unwrap_err().disconnect_reason(),
+        Some(ssh_packet::trans::DisconnectReason::NoMoreAuthMethodsAvailable)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn required_chains_overlap() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let (_, client) = tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie::Cookie::default())
+                        .none(|_| handler::none::Response::Accept)
+                        .password(|_, password: String, _| {
+                            if password == "password" {
+                                handler::password::Response::Accept
+                            } else {
+                                handler::password::Response::Reject
+                            }
+                        })
+                        .required(|_| {
+                            vec![
+                                vec![handler::Method::None, handler::Method::Password],
+                                vec![
+                                    handler::Method::Password,
+                                    handler::Method::KeyboardInteractive,
+                                ],
+                            ]
+                        }),
+                )
+                .await
+                .map(drop)
+        },
+        async {
+            // The `password` method completes the second chain's first step,
+            // and is required again by the first chain after the `none` method.
+            client
+                .request(Scripted(vec![
+                    ("user", Some("password")),
+                    ("user", None),
+                    ("user", Some("password")),
+                ]))
+                .await
+        },
+    )?;
+
+    assert_eq!(client, ["partial", "partial", "success"]);
+
+    Ok(())
+}
+
+/// A bare client abandoning the `keyboard-interactive` exchange for a `password` request.
+struct Abandon;
+
+impl assh::service::Request for Abandon {
+    const SERVICE_NAME: &'static str = "ssh-userauth";
+
+    type Err = assh::Error;
+    type Ok<'s, IO: 's, S: 's> = ();
+
+    async fn on_accept<'s, IO, S>(
+        &mut self,
+        session: &'s mut assh::Session<IO, S>,
+    ) -> Result<Self::Ok<'s, IO, S>, Self::Err>
+    where
+        IO: futures::AsyncBufRead + futures::AsyncWrite + Unpin,
+        S: assh::side::Side,
+    {
+        use ssh_packet::userauth;
+
+        let service_name = <cookie::Cookie as assh::service::Request>::SERVICE_NAME;
+
+        session
+            .send(&userauth::Request {
+                username: "user".into(),
+                service_name: service_name.into(),
+                method: userauth::Method::KeyboardInteractive {
+                    language: Default::default(),
+                    submethods: Default::default(),
+                },
+            })
+            .await?;
+        session.recv().await?.to::<userauth::InfoRequest>()?;
+
+        session
+            .send(&userauth::Request {
+                username: "user".into(),
+                service_name: service_name.into(),
+                method: userauth::Method::Password {
+                    password: "password".into(),
+                    new: None,
+                },
+            })
+            .await?;
+        session.recv().await?.to::<userauth::Success>()?;
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn keyboard_interactive_abandoned() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie::Cookie::default())
+                        .keyboard_interactive(|_, _| {
+                            handler::keyboard_interactive::Response::Challenge {
+                                name: Default::default(),
+                                instruction: Default::default(),
+                                prompts: vec![handler::keyboard_interactive::Prompt::new(
+                                    "Code: ", true,
+                                )],
+                            }
+                        })
+                        .password(|_, password: String, _| {
+                            if password == "password" {
+                                handler::password::Response::Accept
+                            } else {
+                                handler::password::Response::Reject
+                            }
+                        }),
+                )
+                .await
+                .map(drop)
+        },
+        async { client.request(Abandon).await },
+    )?;
+
+    Ok(())
+}
+
+/// A client stalling after the authentication service has been accepted.
+struct Stall;
+
+impl assh::service::Request for Stall {
+    const SERVICE_NAME: &'static str = "ssh-userauth";
+
+    type Err = assh::Error;
+    type Ok<'s, IO: 's, S: 's> = ();
+
+    async fn on_accept<'s, IO, S>(
+        &mut self,
+        session: &'s mut assh::Session<IO, S>,
+    ) -> Result<Self::Ok<'s, IO, S>, Self::Err>
+    where
+        IO: futures::AsyncBufRead + futures::AsyncWrite + Unpin,
+        S: assh::side::Side,
+    {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        session.recv().await.map(drop)
+    }
+}
+
+#[tokio::test]
+async fn grace_time_disconnects() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let (server, client) = tokio::join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie::Cookie::default())
+                        .grace_time(std::time::Duration::from_millis(100)),
+                )
+                .await
+        },
+        async { client.request(Stall).await },
+    );
+
+    let (server, client) = (server.unwrap_err(), client.unwrap_err());
+
+    assert!(matches!(
+        server.disconnect_reason(),
+        Some(ssh_packet::trans::DisconnectReason::ByApplication)
+    ));
+    assert!(matches!(
+        client,
+        assh::Error::Disconnected(assh::error::DisconnectedError {
+            reason: ssh_packet::trans::DisconnectReason::ByApplication,
+            ref description,
+            ..
+        }) if description == "Authentication grace time exceeded"
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn async_password() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut server, mut client) = pair().await?;
+
+    let cookie0 = cookie::Cookie::default();
+    let cookie1 = cookie::Cookie::default();
+
+    tokio::try_join!(
+        async {
+            server
+                .handle(handler::Auth::new(cookie0.clone()).password(
+                    |user: String, password: String, _| async move {
+                        // Simulate a lookup against an external authentication service.
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+                        if user == "user" && password == "password" {
+                            handler::password::Response::Accept
+                        } else {
+                            handler::password::Response::Reject
+                        }
+                    },
+                ))
+                .await
+        },
+        async {
+            client
+                .request(request::Auth::new("user", cookie1.clone()).password("password"))
+                .await
+        },
+    )?;
+
+    assert!(
+        cookie0.is_flagged(),
+        "Authentication handling did not succeed"
+    );
+    assert!(
+        cookie1.is_flagged(),
+        "Authentication request did not succeed"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn identity_is_provided() -> Result<(), Box<dyn std::error::Error>> {
+    use handler::identity::Identity;
+
+    #[derive(Debug, PartialEq)]
+    struct Uid(u32);
+
+    let (mut server, mut client) = pair().await?;
+
+    let host_key =
+        ssh_key::PrivateKey::random(&mut rand::thread_rng(), ssh_key::Algorithm::Ed25519)?;
+    let fingerprint = host_key.public_key().fingerprint(ssh_key::HashAlg::Sha256);
+
+    let ((identity, ()), ()) = tokio::try_join!(
+        async {
+            server
+                .handle(
+                    handler::Auth::new(cookie::Cookie::default())
+                        .hostbased(|_, _, _, _| handler::hostbased::Response::Accept)
+                        .identify(|mut identity: Identity| async move {
+                            identity.context.insert(Uid(1000));
+
+                            identity
+                        }),
+                )
+                .await
+        },
+        async {
+            client
+                .request(
+                    request::Auth::new("user", cookie::Cookie::default()).hostbased(
+                        host_key,
+                        "node.cluster.local",
+                        "scheduler",
+                    ),
+                )
+                .await
+        },
+    )?;
+
+    assert_eq!(identity.username, "user");
+    assert_eq!(identity.method(), Some(handler::Method::Hostbased));
+    assert_eq!(identity.fingerprint(), Some(fingerprint));
+    assert_eq!(identity.context.get::<Uid>(), Some(&Uid(1000)));
+
+    Ok(())
+}
@@ -427,6 +427,8 @@ where
                 "Received an unhandled packet from peer of length `{}` bytes",
                 packet.payload.len()
             );
+
+            self.session.unimplemented().await?;
         }
 
         Ok(())
@@ -0,0 +1,88 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::executor::block_on;
+
+use assh::{
+    algorithm::{Cipher, Compress, Hmac, Policy},
+    io::Tokio,
+    side::{
+        client::{Algorithms, Client},
+        server::Server,
+    },
+    Session,
+};
+use ssh_packet::connect::ChannelData;
+use tokio::io::DuplexStream;
+
+type Pair = (
+    Session<Tokio<DuplexStream>, Client>,
+    Session<Tokio<DuplexStream>, Server>,
+);
+
+fn sessions(cipher: Cipher, hmac: Hmac) -> Pair {
+    let (client, server) = tokio::io::duplex(ssh_packet::PACKET_MAX_SIZE * 4);
+
+    block_on(async {
+        futures::try_join!(
+            Session::new_tokio(
+                client,
+                Client {
+                    algorithms: Algorithms {
+                        ciphers: vec![cipher],
+                        macs: vec![hmac],
+                        compressions: vec![Compress::None],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ),
+            Session::new_tokio(
+                server,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .expect("Unable to generate a server key")],
+                    algorithms: Policy::Legacy.into(),
+                    ..Default::default()
+                },
+            ),
+        )
+    })
+    .expect("Unable to establish the sessions")
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("throughput");
+
+    for (cipher, hmac) in [
+        (Cipher::Aes128Ctr, Hmac::HmacSha256ETM),
+        (Cipher::Aes256Ctr, Hmac::HmacSha512ETM),
+        (Cipher::Aes256Cbc, Hmac::HmacSha256),
+    ] {
+        let name = format!("{}+{}", cipher.as_ref(), hmac.as_ref());
+        let (mut client, mut server) = sessions(cipher, hmac);
+
+        for size in [256, 4096, 32768] {
+            let message = ChannelData {
+                recipient_channel: 0,
+                data: vec![0; size].into(),
+            };
+
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(&name, size), &message, |b, message| {
+                b.iter(|| {
+                    block_on(async {
+                        futures::try_join!(client.send(message), server.recv())
+                            .expect("Unable to transfer the message")
+                    })
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
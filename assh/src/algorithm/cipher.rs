@@ -1,26 +1,27 @@
-use aes_gcm::Tag;
+use cipher::{
+    block_padding::NoPadding, inout::InOutBufReserved, BlockDecryptMut, BlockEncryptMut, KeyIvInit,
+    StreamCipher,
+};
 use ssh_packet::trans::KexInit;
 use strum::{AsRefStr, EnumString};
 
+use super::NegotiationReport;
 use crate::{Error, Result};
 
-// TODO: Get rid of this Box<dyn> altogether.
-pub type CipherState = Box<dyn std::any::Any + Send + Sync>;
-
 pub fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<(Cipher, Cipher)> {
+    let err = || Error::NoCommonCipher(NegotiationReport::new(clientkex, serverkex).into());
+
     Ok((
-        clientkex
-            .encryption_algorithms_client_to_server
-            .preferred_in(&serverkex.encryption_algorithms_client_to_server)
-            .ok_or(Error::NoCommonCipher)?
-            .parse()
-            .map_err(|_| Error::NoCommonCipher)?,
-        clientkex
-            .encryption_algorithms_server_to_client
-            .preferred_in(&serverkex.encryption_algorithms_server_to_client)
-            .ok_or(Error::NoCommonCipher)?
-            .parse()
-            .map_err(|_| Error::NoCommonCipher)?,
+        super::negociate(
+            &clientkex.encryption_algorithms_client_to_server,
+            &serverkex.encryption_algorithms_client_to_server,
+            err,
+        )?,
+        super::negociate(
+            &clientkex.encryption_algorithms_server_to_client,
+            &serverkex.encryption_algorithms_server_to_client,
+            err,
+        )?,
     ))
 }
 
@@ -71,144 +72,6 @@ pub enum Cipher {
 }
 
 impl Cipher {
-    /// This method is a hack to solve deduplication of the enum
-    /// variants and to store the cipher states inside a dynamically
-    /// typed `Box<dyn std::any::Any>`.
-    fn state<'s, T: cipher::KeyIvInit + Send + Sync + 'static>(
-        state: &'s mut Option<CipherState>,
-        key: &[u8],
-        iv: &[u8],
-    ) -> &'s mut T {
-        state
-            .get_or_insert_with(|| {
-                Box::new(T::new_from_slices(key, iv).expect("Key derivation failed horribly"))
-            })
-            .downcast_mut()
-            .expect("State changed in the meanwhile")
-    }
-
-    fn ctr<C: ctr::cipher::StreamCipher>(cipher: &mut C, buffer: &mut [u8]) -> Result<Option<Tag>> {
-        cipher
-            .try_apply_keystream(buffer)
-            .map_err(|_| Error::Cipher)?;
-
-        Ok(None)
-    }
-
-    pub(crate) fn encrypt(
-        &mut self,
-        state: &mut Option<CipherState>,
-        key: &[u8],
-        iv: &[u8],
-        buffer: &mut [u8],
-    ) -> Result<Option<Tag>> {
-        fn cbc<C: cbc::cipher::BlockEncryptMut>(
-            cipher: &mut C,
-            buffer: &mut [u8],
-        ) -> Result<Option<Tag>> {
-            use cbc::cipher::inout;
-
-            let data = inout::InOutBufReserved::from_mut_slice(buffer, buffer.len())
-                .map_err(|_| Error::Cipher)?;
-
-            let mut buf = data
-                .into_padded_blocks::<cbc::cipher::block_padding::NoPadding, C::BlockSize>()
-                .map_err(|_| Error::Cipher)?;
-
-            cipher.encrypt_blocks_inout_mut(buf.get_blocks());
-            if let Some(block) = buf.get_tail_block() {
-                cipher.encrypt_block_inout_mut(block);
-            }
-
-            Ok(None)
-        }
-
-        match self {
-            Self::Aes256Ctr => Self::ctr(
-                Self::state::<ctr::Ctr128BE<aes::Aes256>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes192Ctr => Self::ctr(
-                Self::state::<ctr::Ctr128BE<aes::Aes192>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes128Ctr => Self::ctr(
-                Self::state::<ctr::Ctr128BE<aes::Aes128>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes256Cbc => cbc(
-                Self::state::<cbc::Encryptor<aes::Aes256>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes192Cbc => cbc(
-                Self::state::<cbc::Encryptor<aes::Aes192>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes128Cbc => cbc(
-                Self::state::<cbc::Encryptor<aes::Aes128>>(state, key, iv),
-                buffer,
-            ),
-            Self::TDesCbc => cbc(
-                Self::state::<cbc::Encryptor<des::TdesEde3>>(state, key, iv),
-                buffer,
-            ),
-            Self::None => Ok(None),
-        }
-    }
-
-    pub(crate) fn decrypt(
-        &mut self,
-        state: &mut Option<CipherState>,
-        key: &[u8],
-        iv: &[u8],
-        buffer: &mut [u8],
-    ) -> Result<Option<Tag>> {
-        fn cbc<C: cbc::cipher::BlockDecryptMut>(
-            cipher: &mut C,
-            buffer: &mut [u8],
-        ) -> Result<Option<Tag>> {
-            use cbc::cipher::inout;
-
-            let data = inout::InOutBufReserved::from_mut_slice(buffer, buffer.len())
-                .map_err(|_| Error::Cipher)?;
-
-            let mut buf = data
-                .into_padded_blocks::<cbc::cipher::block_padding::NoPadding, C::BlockSize>()
-                .map_err(|_| Error::Cipher)?;
-
-            cipher.decrypt_blocks_inout_mut(buf.get_blocks());
-            if let Some(block) = buf.get_tail_block() {
-                cipher.decrypt_block_inout_mut(block);
-            }
-
-            Ok(None)
-        }
-
-        match self {
-            // In CTR mode, encryption and decrytion are the same
-            Self::Aes256Ctr | Self::Aes192Ctr | Self::Aes128Ctr => {
-                self.encrypt(state, key, iv, buffer)
-            }
-            Self::Aes256Cbc => cbc(
-                Self::state::<cbc::Decryptor<aes::Aes256>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes192Cbc => cbc(
-                Self::state::<cbc::Decryptor<aes::Aes192>>(state, key, iv),
-                buffer,
-            ),
-            Self::Aes128Cbc => cbc(
-                Self::state::<cbc::Decryptor<aes::Aes128>>(state, key, iv),
-                buffer,
-            ),
-            Self::TDesCbc => cbc(
-                Self::state::<cbc::Decryptor<des::TdesEde3>>(state, key, iv),
-                buffer,
-            ),
-            Self::None => Ok(None),
-        }
-    }
-
     pub(crate) fn block_size(&self) -> usize {
         match self {
             Self::None | Self::TDesCbc { .. } => 8,
@@ -256,3 +119,178 @@ impl Cipher {
     //     }
     // }
 }
+
+/// The initialized state of a [`Cipher`] for a single direction,
+/// created once per key-exchange from the derived key and IV.
+pub enum CipherState {
+    None,
+    Aes256Ctr(ctr::Ctr128BE<aes::Aes256>),
+    Aes192Ctr(ctr::Ctr128BE<aes::Aes192>),
+    Aes128Ctr(ctr::Ctr128BE<aes::Aes128>),
+    Aes256CbcEncryptor(cbc::Encryptor<aes::Aes256>),
+    Aes192CbcEncryptor(cbc::Encryptor<aes::Aes192>),
+    Aes128CbcEncryptor(cbc::Encryptor<aes::Aes128>),
+    TDesCbcEncryptor(cbc::Encryptor<des::TdesEde3>),
+    Aes256CbcDecryptor(cbc::Decryptor<aes::Aes256>),
+    Aes192CbcDecryptor(cbc::Decryptor<aes::Aes192>),
+    Aes128CbcDecryptor(cbc::Decryptor<aes::Aes128>),
+    TDesCbcDecryptor(cbc::Decryptor<des::TdesEde3>),
+}
+
+impl CipherState {
+    fn init<T: KeyIvInit>(key: &[u8], iv: &[u8]) -> Result<T> {
+        T::new_from_slices(key, iv).map_err(|_| Error::Cipher)
+    }
+
+    /// Initialize the state to encrypt with the `cipher`.
+    pub(crate) fn encryptor(cipher: &Cipher, key: &[u8], iv: &[u8]) -> Result<Self> {
+        Ok(match cipher {
+            Cipher::Aes256Ctr => Self::Aes256Ctr(Self::init(key, iv)?),
+            Cipher::Aes192Ctr => Self::Aes192Ctr(Self::init(key, iv)?),
+            Cipher::Aes128Ctr => Self::Aes128Ctr(Self::init(key, iv)?),
+            Cipher::Aes256Cbc => Self::Aes256CbcEncryptor(Self::init(key, iv)?),
+            Cipher::Aes192Cbc => Self::Aes192CbcEncryptor(Self::init(key, iv)?),
+            Cipher::Aes128Cbc => Self::Aes128CbcEncryptor(Self::init(key, iv)?),
+            Cipher::TDesCbc => Self::TDesCbcEncryptor(Self::init(key, iv)?),
+            Cipher::None => Self::None,
+        })
+    }
+
+    /// Initialize the state to decrypt with the `cipher`.
+    pub(crate) fn decryptor(cipher: &Cipher, key: &[u8], iv: &[u8]) -> Result<Self> {
+        Ok(match cipher {
+            // In CTR mode, encryption and decrytion are the same
+            Cipher::Aes256Ctr | Cipher::Aes192Ctr | Cipher::Aes128Ctr => {
+                Self::encryptor(cipher, key, iv)?
+            }
+            Cipher::Aes256Cbc => Self::Aes256CbcDecryptor(Self::init(key, iv)?),
+            Cipher::Aes192Cbc => Self::Aes192CbcDecryptor(Self::init(key, iv)?),
+            Cipher::Aes128Cbc => Self::Aes128CbcDecryptor(Self::init(key, iv)?),
+            Cipher::TDesCbc => Self::TDesCbcDecryptor(Self::init(key, iv)?),
+            Cipher::None => Self::None,
+        })
+    }
+
+    /// Encrypt the `buffer` in-place, which must be a multiple of the block size.
+    pub(crate) fn encrypt(&mut self, buffer: &mut [u8]) -> Result<()> {
+        fn cbc<C: BlockEncryptMut>(cipher: &mut C, buffer: &mut [u8]) -> Result<()> {
+            let data = InOutBufReserved::from_mut_slice(buffer, buffer.len())
+                .map_err(|_| Error::Cipher)?;
+
+            let mut buf = data
+                .into_padded_blocks::<NoPadding, C::BlockSize>()
+                .map_err(|_| Error::Cipher)?;
+
+            cipher.encrypt_blocks_inout_mut(buf.get_blocks());
+            if let Some(block) = buf.get_tail_block() {
+                cipher.encrypt_block_inout_mut(block);
+            }
+
+            Ok(())
+        }
+
+        match self {
+            Self::Aes256Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes192Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes128Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes256CbcEncryptor(cipher) => cbc(cipher, buffer),
+            Self::Aes192CbcEncryptor(cipher) => cbc(cipher, buffer),
+            Self::Aes128CbcEncryptor(cipher) => cbc(cipher, buffer),
+            Self::TDesCbcEncryptor(cipher) => cbc(cipher, buffer),
+            Self::None => Ok(()),
+            _ => Err(Error::Cipher),
+        }
+    }
+
+    /// Decrypt the `buffer` in-place, which must be a multiple of the block size.
+    pub(crate) fn decrypt(&mut self, buffer: &mut [u8]) -> Result<()> {
+        fn cbc<C: BlockDecryptMut>(cipher: &mut C, buffer: &mut [u8]) -> Result<()> {
+            let data = InOutBufReserved::from_mut_slice(buffer, buffer.len())
+                .map_err(|_| Error::Cipher)?;
+
+            let mut buf = data
+                .into_padded_blocks::<NoPadding, C::BlockSize>()
+                .map_err(|_| Error::Cipher)?;
+
+            cipher.decrypt_blocks_inout_mut(buf.get_blocks());
+            if let Some(block) = buf.get_tail_block() {
+                cipher.decrypt_block_inout_mut(block);
+            }
+
+            Ok(())
+        }
+
+        match self {
+            Self::Aes256Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes192Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes128Ctr(cipher) => ctr(cipher, buffer),
+            Self::Aes256CbcDecryptor(cipher) => cbc(cipher, buffer),
+            Self::Aes192CbcDecryptor(cipher) => cbc(cipher, buffer),
+            Self::Aes128CbcDecryptor(cipher) => cbc(cipher, buffer),
+            Self::TDesCbcDecryptor(cipher) => cbc(cipher, buffer),
+            Self::None => Ok(()),
+            _ => Err(Error::Cipher),
+        }
+    }
+}
+
+fn ctr<C: StreamCipher>(cipher: &mut C, buffer: &mut [u8]) -> Result<()> {
+    cipher
+        .try_apply_keystream(buffer)
+        .map_err(|_| Error::Cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Cipher::Aes256Ctr)]
+    #[case(Cipher::Aes128Ctr)]
+    #[case(Cipher::Aes192Cbc)]
+    #[case(Cipher::TDesCbc)]
+    fn it_roundtrips(#[case] cipher: Cipher) {
+        let key = vec![0x42; cipher.key_size()];
+        let iv = vec![0x24; cipher.iv_size()];
+        let plaintext = vec![0xAA; cipher.block_size() * 4];
+
+        let mut encryptor = CipherState::encryptor(&cipher, &key, &iv).unwrap();
+        let mut decryptor = CipherState::decryptor(&cipher, &key, &iv).unwrap();
+
+        // Encrypt in two halves, to ensure the state is carried over.
+        let mut buffer = plaintext.clone();
+        let (head, tail) = buffer.split_at_mut(cipher.block_size() * 2);
+        encryptor.encrypt(head).unwrap();
+        encryptor.encrypt(tail).unwrap();
+        assert_ne!(buffer, plaintext);
+
+        decryptor.decrypt(&mut buffer).unwrap();
+        assert_eq!(buffer, plaintext);
+    }
+
+    #[test]
+    fn it_rejects_invalid_keys() {
+        assert!(matches!(
+            CipherState::encryptor(&Cipher::Aes256Ctr, &[0; 16], &[0; 16]),
+            Err(Error::Cipher)
+        ));
+        assert!(matches!(
+            CipherState::decryptor(&Cipher::Aes128Cbc, &[0; 16], &[0; 8]),
+            Err(Error::Cipher)
+        ));
+    }
+
+    #[test]
+    fn it_rejects_wrong_direction() {
+        let mut encryptor = CipherState::encryptor(&Cipher::Aes128Cbc, &[0; 16], &[0; 16]).unwrap();
+
+        assert!(matches!(
+            encryptor.decrypt(&mut [0; 16]),
+            Err(Error::Cipher)
+        ));
+    }
+}
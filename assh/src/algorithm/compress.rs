@@ -1,24 +1,28 @@
-use std::io::{Read, Write};
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+};
 
 use ssh_packet::trans::KexInit;
 use strum::{AsRefStr, EnumString};
 
+use super::NegotiationReport;
 use crate::{Error, Result};
 
 pub fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<(Compress, Compress)> {
+    let err = || Error::NoCommonCompression(NegotiationReport::new(clientkex, serverkex).into());
+
     Ok((
-        clientkex
-            .compression_algorithms_client_to_server
-            .preferred_in(&serverkex.compression_algorithms_client_to_server)
-            .ok_or(Error::NoCommonCompression)?
-            .parse()
-            .map_err(|_| Error::NoCommonCompression)?,
-        clientkex
-            .compression_algorithms_server_to_client
-            .preferred_in(&serverkex.compression_algorithms_server_to_client)
-            .ok_or(Error::NoCommonCompression)?
-            .parse()
-            .map_err(|_| Error::NoCommonCompression)?,
+        super::negociate(
+            &clientkex.compression_algorithms_client_to_server,
+            &serverkex.compression_algorithms_client_to_server,
+            err,
+        )?,
+        super::negociate(
+            &clientkex.compression_algorithms_server_to_client,
+            &serverkex.compression_algorithms_server_to_client,
+            err,
+        )?,
     ))
 }
 
@@ -42,30 +46,37 @@ pub enum Compress {
 }
 
 impl Compress {
-    pub(crate) fn decompress(&self, buf: Vec<u8>) -> Result<Vec<u8>> {
+    /// Decompress the `buf`, refusing to produce more than `max` bytes.
+    pub(crate) fn decompress(&self, buf: &[u8], max: usize) -> Result<Vec<u8>> {
         match self {
             Self::ZlibOpenssh | Self::Zlib => {
                 let mut buffer = Vec::with_capacity(buf.len());
                 let decoder = libflate::zlib::Decoder::new(std::io::Cursor::new(buf))?;
 
-                decoder
-                    .take(ssh_packet::PACKET_MAX_SIZE as u64)
-                    .read_to_end(&mut buffer)?;
+                decoder.take(max as u64 + 1).read_to_end(&mut buffer)?;
+
+                if buffer.len() > max {
+                    return Err(Error::LimitExceeded {
+                        what: "decompressed payload length",
+                        value: buffer.len(),
+                        max,
+                    });
+                }
 
                 Ok(buffer)
             }
-            Self::None => Ok(buf),
+            Self::None => Ok(buf.to_vec()),
         }
     }
 
-    pub(crate) fn compress(&self, buf: &[u8]) -> Result<Vec<u8>> {
+    pub(crate) fn compress<'b>(&self, buf: &'b [u8]) -> Result<Cow<'b, [u8]>> {
         match self {
             Self::ZlibOpenssh | Self::Zlib => {
                 let mut encoder = libflate::zlib::Encoder::new(Vec::with_capacity(buf.len()))?;
 
                 encoder.write_all(buf)?;
 
-                Ok(encoder.finish().into_result()?)
+                Ok(encoder.finish().into_result()?.into())
             }
             Self::None => Ok(buf.into()),
         }
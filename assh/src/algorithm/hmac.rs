@@ -5,22 +5,23 @@ use sha2::{Sha256, Sha512};
 use ssh_packet::trans::KexInit;
 use strum::{AsRefStr, EnumString};
 
+use super::NegotiationReport;
 use crate::{Error, Result};
 
 pub fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<(Hmac, Hmac)> {
+    let err = || Error::NoCommonHmac(NegotiationReport::new(clientkex, serverkex).into());
+
     Ok((
-        clientkex
-            .mac_algorithms_client_to_server
-            .preferred_in(&serverkex.mac_algorithms_client_to_server)
-            .ok_or(Error::NoCommonHmac)?
-            .parse()
-            .map_err(|_| Error::NoCommonHmac)?,
-        clientkex
-            .mac_algorithms_server_to_client
-            .preferred_in(&serverkex.mac_algorithms_server_to_client)
-            .ok_or(Error::NoCommonHmac)?
-            .parse()
-            .map_err(|_| Error::NoCommonHmac)?,
+        super::negociate(
+            &clientkex.mac_algorithms_client_to_server,
+            &serverkex.mac_algorithms_client_to_server,
+            err,
+        )?,
+        super::negociate(
+            &clientkex.mac_algorithms_server_to_client,
+            &serverkex.mac_algorithms_server_to_client,
+            err,
+        )?,
     ))
 }
 
@@ -98,23 +99,35 @@ impl Hmac {
         }
     }
 
-    pub(crate) fn sign(&self, seq: u32, buf: &[u8], key: &[u8]) -> Vec<u8> {
-        fn sign<D: digest::Mac + digest::KeyInit>(seq: u32, buf: &[u8], key: &[u8]) -> Vec<u8> {
-            <D as digest::Mac>::new_from_slice(key)
-                .expect("Key derivation failed horribly")
-                .chain_update(seq.to_be_bytes())
-                .chain_update(buf)
-                .finalize()
-                .into_bytes()
-                .to_vec()
+    /// Compute the _Message Authentication Code_ of the `buf` into `mac`,
+    /// which must be exactly [`ssh_packet::Mac::size`] bytes long.
+    pub(crate) fn sign(&self, seq: u32, buf: &[u8], key: &[u8], mac: &mut [u8]) {
+        fn sign<D: digest::Mac + digest::KeyInit>(
+            seq: u32,
+            buf: &[u8],
+            key: &[u8],
+            mac: &mut [u8],
+        ) {
+            mac.copy_from_slice(
+                &<D as digest::Mac>::new_from_slice(key)
+                    .expect("Key derivation failed horribly")
+                    .chain_update(seq.to_be_bytes())
+                    .chain_update(buf)
+                    .finalize()
+                    .into_bytes(),
+            )
         }
 
         match self {
-            Self::HmacSha512ETM | Self::HmacSha512 => sign::<hmac::Hmac<Sha512>>(seq, buf, key),
-            Self::HmacSha256ETM | Self::HmacSha256 => sign::<hmac::Hmac<Sha256>>(seq, buf, key),
-            Self::HmacSha1ETM | Self::HmacSha1 => sign::<hmac::Hmac<Sha1>>(seq, buf, key),
-            Self::HmacMd5ETM | Self::HmacMd5 => sign::<hmac::Hmac<Md5>>(seq, buf, key),
-            Self::None => Default::default(),
+            Self::HmacSha512ETM | Self::HmacSha512 => {
+                sign::<hmac::Hmac<Sha512>>(seq, buf, key, mac)
+            }
+            Self::HmacSha256ETM | Self::HmacSha256 => {
+                sign::<hmac::Hmac<Sha256>>(seq, buf, key, mac)
+            }
+            Self::HmacSha1ETM | Self::HmacSha1 => sign::<hmac::Hmac<Sha1>>(seq, buf, key, mac),
+            Self::HmacMd5ETM | Self::HmacMd5 => sign::<hmac::Hmac<Md5>>(seq, buf, key, mac),
+            Self::None => (),
         }
     }
 }
@@ -1,5 +1,5 @@
 use futures::{AsyncBufRead, AsyncWrite};
-use signature::{SignatureEncoding, Signer, Verifier};
+use signature::Verifier;
 use ssh_key::{PrivateKey, Signature};
 use ssh_packet::{
     arch::MpInt,
@@ -15,15 +15,15 @@ use crate::{
     Error, Result,
 };
 
-use super::{cipher, compress, hmac};
+use super::{
+    cipher::{self, CipherState},
+    compress, hmac, key, NegotiationReport,
+};
 
 pub fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<Kex> {
-    clientkex
-        .kex_algorithms
-        .preferred_in(&serverkex.kex_algorithms)
-        .ok_or(Error::NoCommonKex)?
-        .parse()
-        .map_err(|_| Error::NoCommonKex)
+    let err = || Error::NoCommonKex(NegotiationReport::new(clientkex, serverkex).into());
+
+    super::negociate(&clientkex.kex_algorithms, &serverkex.kex_algorithms, err)
 }
 
 // TODO: Implement the following legacy key-exchange methods (`diffie-hellman-group14-sha256`, `diffie-hellman-group14-sha1`, `diffie-hellman-group1-sha1`).
@@ -64,7 +64,7 @@ impl Kex {
             Self::Curve25519Sha256 | Self::Curve25519Sha256Libssh => {
                 type Hash = sha2::Sha256;
 
-                let e_c = x25519_dalek::EphemeralSecret::random_from_rng(rand::thread_rng());
+                let e_c = x25519_dalek::EphemeralSecret::random_from_rng(stream.rng());
                 let q_c = x25519_dalek::PublicKey::from(&e_c);
 
                 stream
@@ -103,10 +103,12 @@ impl Kex {
 
                 Verifier::verify(&k_s, &hash, &Signature::try_from(&*ecdh.signature)?)?;
 
+                let rng = stream.rng().clone();
                 let session_id = stream.with_session(&hash);
 
-                Ok(TransportPair {
+                let transport = TransportPair {
                     rx: Transport {
+                        rng: rng.clone(),
                         chain: Keys::as_server::<Hash>(
                             &secret,
                             &hash,
@@ -114,12 +116,13 @@ impl Kex {
                             &client_cipher,
                             &client_hmac,
                         ),
-                        state: None,
+                        state: CipherState::None,
                         cipher: client_cipher,
                         hmac: client_hmac,
                         compress: client_compress,
                     },
                     tx: Transport {
+                        rng: rng.clone(),
                         chain: Keys::as_client::<Hash>(
                             &secret,
                             &hash,
@@ -127,12 +130,15 @@ impl Kex {
                             &server_cipher,
                             &server_hmac,
                         ),
-                        state: None,
+                        state: CipherState::None,
                         cipher: server_cipher,
                         hmac: server_hmac,
                         compress: server_compress,
                     },
-                })
+                };
+                stream.log_keys(&hash, &secret, &transport.tx, &transport.rx);
+
+                Ok(transport)
             }
         }
     }
@@ -146,6 +152,7 @@ impl Kex {
         i_s: KexInit,
         key: &PrivateKey,
     ) -> Result<TransportPair> {
+        let keyalg = key::negociate(&i_c, &i_s)?;
         let (client_hmac, server_hmac) = hmac::negociate(&i_c, &i_s)?;
         let (client_compress, server_compress) = compress::negociate(&i_c, &i_s)?;
         let (client_cipher, server_cipher) = cipher::negociate(&i_c, &i_s)?;
@@ -156,7 +163,7 @@ impl Kex {
 
                 let ecdh: KexEcdhInit = stream.recv().await?.to()?;
 
-                let e_s = x25519_dalek::EphemeralSecret::random_from_rng(rand::thread_rng());
+                let e_s = x25519_dalek::EphemeralSecret::random_from_rng(stream.rng());
                 let q_s = x25519_dalek::PublicKey::from(&e_s);
 
                 let q_c = x25519_dalek::PublicKey::from(
@@ -188,19 +195,21 @@ impl Kex {
                 };
                 let hash = exchange.hash::<Hash>();
 
-                let signature = Signer::sign(key, &hash);
+                let signature = key::sign(key, &keyalg, &hash)?;
                 stream
                     .send(&KexEcdhReply {
                         k_s,
                         q_s,
-                        signature: signature.to_vec().into(),
+                        signature: signature.into(),
                     })
                     .await?;
 
+                let rng = stream.rng().clone();
                 let session_id = stream.with_session(&hash);
 
-                Ok(TransportPair {
+                let transport = TransportPair {
                     rx: Transport {
+                        rng: rng.clone(),
                         chain: Keys::as_client::<Hash>(
                             &secret,
                             &hash,
@@ -208,12 +217,13 @@ impl Kex {
                             &client_cipher,
                             &client_hmac,
                         ),
-                        state: None,
+                        state: CipherState::None,
                         cipher: client_cipher,
                         hmac: client_hmac,
                         compress: client_compress,
                     },
                     tx: Transport {
+                        rng: rng.clone(),
                         chain: Keys::as_server::<Hash>(
                             &secret,
                             &hash,
@@ -221,12 +231,15 @@ impl Kex {
                             &server_cipher,
                             &server_hmac,
                         ),
-                        state: None,
+                        state: CipherState::None,
                         cipher: server_cipher,
                         hmac: server_hmac,
                         compress: server_compress,
                     },
-                })
+                };
+                stream.log_keys(&hash, &secret, &transport.rx, &transport.tx);
+
+                Ok(transport)
             }
         }
     }
@@ -1,13 +1,88 @@
+//! Host and user key algorithms, and the signatures they produce.
+
 pub use ssh_key::Algorithm as Key;
+
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use signature::{SignatureEncoding, Signer};
+use ssh_key::{private::KeypairData, HashAlg, PrivateKey, Signature};
 use ssh_packet::trans::KexInit;
 
+use super::NegotiationReport;
 use crate::{Error, Result};
 
-pub fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<Key> {
-    clientkex
-        .server_host_key_algorithms
-        .preferred_in(&serverkex.server_host_key_algorithms)
-        .ok_or(Error::NoCommonKey)?
-        .parse()
-        .map_err(|_| Error::NoCommonKex)
+pub(crate) fn negociate(clientkex: &KexInit, serverkex: &KexInit) -> Result<Key> {
+    let err = || Error::NoCommonKey(NegotiationReport::new(clientkex, serverkex).into());
+
+    super::negociate(
+        &clientkex.server_host_key_algorithms,
+        &serverkex.server_host_key_algorithms,
+        err,
+    )
+}
+
+/// Whether a key of the `key` algorithm is able to produce signatures for the `algorithm`,
+/// since a single RSA key can sign with multiple hash algorithms.
+pub fn is_compatible(key: &Key, algorithm: &Key) -> bool {
+    match (key, algorithm) {
+        (Key::Rsa { .. }, Key::Rsa { .. }) => true,
+        (key, algorithm) => key == algorithm,
+    }
+}
+
+/// Sign the `message` with the `key` using the `algorithm`, which must be compatible with the key,
+/// producing an encoded signature blob.
+pub fn sign(key: &PrivateKey, algorithm: &Key, message: &[u8]) -> Result<Vec<u8>> {
+    fn rsa<D: digest::Digest + digest::const_oid::AssociatedOid>(
+        keypair: &ssh_key::private::RsaKeypair,
+        message: &[u8],
+    ) -> Result<Vec<u8>> {
+        fn uint(mpint: &ssh_key::Mpint) -> Result<rsa::BigUint> {
+            Ok(rsa::BigUint::from_bytes_be(
+                mpint.as_positive_bytes().ok_or(ssh_key::Error::Crypto)?,
+            ))
+        }
+
+        // The `TryFrom<&RsaKeypair> for rsa::RsaPrivateKey` conversion of `ssh-key` 0.6.7
+        // passes the `p` factor twice instead of `p` and `q` (see `src/private/rsa.rs`),
+        // so the key is rebuilt from it's components here until it is fixed upstream.
+        let key = rsa::RsaPrivateKey::from_components(
+            uint(&keypair.public.n)?,
+            uint(&keypair.public.e)?,
+            uint(&keypair.private.d)?,
+            vec![uint(&keypair.private.p)?, uint(&keypair.private.q)?],
+        )
+        .map_err(|_| ssh_key::Error::Crypto)?;
+
+        Ok(rsa::pkcs1v15::SigningKey::<D>::new(key)
+            .try_sign(message)?
+            .to_vec())
+    }
+
+    match (key.key_data(), algorithm) {
+        (KeypairData::Rsa(keypair), Key::Rsa { hash }) => {
+            let data = match hash {
+                Some(HashAlg::Sha512) => rsa::<Sha512>(keypair, message)?,
+                Some(HashAlg::Sha256) => rsa::<Sha256>(keypair, message)?,
+                None => rsa::<Sha1>(keypair, message)?,
+                Some(_) => {
+                    return Err(ssh_key::Error::AlgorithmUnsupported {
+                        algorithm: algorithm.clone(),
+                    }
+                    .into())
+                }
+            };
+
+            // The `ssh-key` crate is unable to represent `ssh-rsa` signatures,
+            // so the signature blob is encoded by hand here.
+            let mut blob = Vec::new();
+            for field in [algorithm.as_str().as_bytes(), &data] {
+                blob.extend_from_slice(&(field.len() as u32).to_be_bytes());
+                blob.extend_from_slice(field);
+            }
+
+            Ok(blob)
+        }
+        _ => Ok(Signer::<Signature>::try_sign(key, message)?.to_vec()),
+    }
 }
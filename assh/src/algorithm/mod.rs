@@ -1,5 +1,9 @@
 //! Supported algorithms for **compression**, **encryption**, **integrity** and **key-exchange**.
 
+use ssh_packet::arch::NameList;
+
+use crate::{Error, Result};
+
 // TODO: Gate insecure algorithms behind an `insecure` feature flag.
 
 mod cipher;
@@ -15,5 +19,24 @@ pub use hmac::Hmac;
 pub(crate) mod kex;
 pub use kex::Kex;
 
-pub(crate) mod key;
+pub mod key;
 pub use key::Key;
+
+pub mod policy;
+pub use policy::Policy;
+
+pub mod report;
+pub use report::NegotiationReport;
+
+/// Negociate the first algorithm of the `client`'s list that is also in the `server`'s one,
+/// failing with the `err` otherwise.
+fn negociate<T: std::str::FromStr>(
+    client: &NameList,
+    server: &NameList,
+    err: impl FnOnce() -> Error,
+) -> Result<T> {
+    client
+        .preferred_in(server)
+        .and_then(|algorithm| algorithm.parse().ok())
+        .ok_or_else(err)
+}
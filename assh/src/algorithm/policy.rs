@@ -0,0 +1,331 @@
+//! Named algorithm presets and validation of the enabled algorithms.
+//!
+//! The presets mirror the [Mozilla OpenSSH guidelines] and the [ssh-audit] hardening
+//! recommendations, restricted to the algorithms implemented by this crate.
+//!
+//! [Mozilla OpenSSH guidelines]: https://infosec.mozilla.org/guidelines/openssh
+//! [ssh-audit]: https://www.ssh-audit.com/hardening_guides.html
+
+use ssh_key::{EcdsaCurve, HashAlg};
+
+use super::{Cipher, Compress, Hmac, Kex, Key};
+use crate::{Error, Result};
+
+/// A named set of algorithms, to build either
+/// [`client::Algorithms`](crate::side::client::Algorithms) or
+/// [`server::Algorithms`](crate::side::server::Algorithms) from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Only the strongest algorithms, for peers running a recent OpenSSH.
+    Modern,
+
+    /// Strong algorithms, still interoperable with most of the peers of the last decade.
+    Compatible,
+
+    /// Only host key, cipher and MAC algorithms approved in FIPS 140-2.
+    ///
+    /// # Note
+    /// This is **not** a FIPS 140-2 compliant configuration, since none of the approved
+    /// _key-exchange_ algorithms are implemented yet, the non-approved `curve25519-sha256` is used instead.
+    Fips,
+
+    /// Every implemented algorithm, including the broken ones, for ancient peers.
+    Legacy,
+}
+
+impl Policy {
+    pub(crate) fn kexs(self) -> Vec<Kex> {
+        match self {
+            Self::Modern => vec![Kex::Curve25519Sha256],
+            Self::Compatible | Self::Fips | Self::Legacy => {
+                vec![Kex::Curve25519Sha256, Kex::Curve25519Sha256Libssh]
+            }
+        }
+    }
+
+    pub(crate) fn keys(self) -> Vec<Key> {
+        let ed25519 = [Key::Ed25519];
+        let ecdsa = [
+            Key::Ecdsa {
+                curve: EcdsaCurve::NistP521,
+            },
+            Key::Ecdsa {
+                curve: EcdsaCurve::NistP384,
+            },
+            Key::Ecdsa {
+                curve: EcdsaCurve::NistP256,
+            },
+        ];
+        let rsa = [
+            Key::Rsa {
+                hash: Some(HashAlg::Sha512),
+            },
+            Key::Rsa {
+                hash: Some(HashAlg::Sha256),
+            },
+        ];
+        let legacy = [Key::Rsa { hash: None }, Key::Dsa];
+
+        match self {
+            Self::Modern => [&ed25519[..], &rsa].concat(),
+            Self::Compatible => [&ed25519[..], &ecdsa, &rsa].concat(),
+            Self::Fips => [&ecdsa[..], &rsa].concat(),
+            Self::Legacy => [&ed25519[..], &ecdsa, &rsa, &legacy].concat(),
+        }
+    }
+
+    pub(crate) fn ciphers(self) -> Vec<Cipher> {
+        let ctr = || [Cipher::Aes256Ctr, Cipher::Aes192Ctr, Cipher::Aes128Ctr];
+        let cbc = || [Cipher::Aes256Cbc, Cipher::Aes192Cbc, Cipher::Aes128Cbc];
+
+        match self {
+            Self::Modern | Self::Compatible | Self::Fips => ctr().into(),
+            Self::Legacy => ctr()
+                .into_iter()
+                .chain(cbc())
+                .chain([Cipher::TDesCbc])
+                .collect(),
+        }
+    }
+
+    pub(crate) fn macs(self) -> Vec<Hmac> {
+        let etm = || [Hmac::HmacSha512ETM, Hmac::HmacSha256ETM];
+        let eam = || [Hmac::HmacSha512, Hmac::HmacSha256];
+
+        match self {
+            Self::Modern => etm().into(),
+            Self::Compatible | Self::Fips => etm().into_iter().chain(eam()).collect(),
+            Self::Legacy => etm()
+                .into_iter()
+                .chain(eam())
+                .chain([
+                    Hmac::HmacSha1ETM,
+                    Hmac::HmacSha1,
+                    Hmac::HmacMd5ETM,
+                    Hmac::HmacMd5,
+                ])
+                .collect(),
+        }
+    }
+
+    pub(crate) fn compressions(self) -> Vec<Compress> {
+        // The `zlib` algorithm compresses before authentication, exposing the decompressor to anyone.
+        match self {
+            Self::Modern | Self::Fips => vec![Compress::ZlibOpenssh, Compress::None],
+            Self::Compatible | Self::Legacy => {
+                vec![Compress::ZlibOpenssh, Compress::Zlib, Compress::None]
+            }
+        }
+    }
+}
+
+/// How serious a [`Finding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The algorithm is considered weak, but still usable with outdated peers.
+    Weak,
+
+    /// The algorithm or the combination is insecure or unusable, and is refused.
+    Insecure,
+}
+
+/// A weakness found while validating the enabled algorithms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// How serious the weakness is.
+    pub severity: Severity,
+
+    /// The name of the offending algorithm, or of the empty category.
+    pub algorithm: String,
+
+    /// A human-readable description of the weakness.
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} `{}`: {}",
+            self.severity, self.algorithm, self.reason
+        )
+    }
+}
+
+/// Validate the enabled algorithms, returning the weak ones,
+/// or an [`Error::InsecureAlgorithms`] if any of them is insecure.
+pub(crate) fn validate(
+    kexs: &[Kex],
+    keys: &[Key],
+    ciphers: &[Cipher],
+    macs: &[Hmac],
+    compressions: &[Compress],
+) -> Result<Vec<Finding>> {
+    let (insecure, weak) = audit(kexs, keys, ciphers, macs, compressions)
+        .into_iter()
+        .partition::<Vec<_>, _>(|finding| finding.severity == Severity::Insecure);
+
+    if insecure.is_empty() {
+        Ok(weak)
+    } else {
+        Err(Error::InsecureAlgorithms(insecure))
+    }
+}
+
+/// Audit the enabled algorithms for weaknesses, either on their own or in combination.
+fn audit(
+    kexs: &[Kex],
+    keys: &[Key],
+    ciphers: &[Cipher],
+    macs: &[Hmac],
+    compressions: &[Compress],
+) -> Vec<Finding> {
+    let finding = |severity, algorithm: &str, reason| Finding {
+        severity,
+        algorithm: algorithm.into(),
+        reason,
+    };
+
+    let empty = [
+        ("kex", kexs.is_empty()),
+        ("key", keys.is_empty()),
+        ("cipher", ciphers.is_empty()),
+        ("mac", macs.is_empty()),
+        ("compression", compressions.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(_, empty)| *empty)
+    .map(|(category, _)| {
+        finding(
+            Severity::Insecure,
+            category,
+            "no algorithm enabled, the negociation would always fail",
+        )
+    });
+
+    let weak_keys = keys.iter().filter_map(|key| match key {
+        Key::Dsa => Some(finding(
+            Severity::Weak,
+            key.as_str(),
+            "DSA keys are limited to 1024 bits and deprecated",
+        )),
+        Key::Rsa { hash: None } => Some(finding(
+            Severity::Weak,
+            key.as_str(),
+            "signatures are computed over SHA-1, which is vulnerable to collisions",
+        )),
+        _ => None,
+    });
+
+    let weak_ciphers = ciphers.iter().filter_map(|cipher| match cipher {
+        Cipher::None => Some(finding(
+            Severity::Insecure,
+            cipher.as_ref(),
+            "packets would be sent unencrypted",
+        )),
+        Cipher::TDesCbc => Some(finding(
+            Severity::Weak,
+            cipher.as_ref(),
+            "64-bit block size, vulnerable to SWEET32",
+        )),
+        _ => None,
+    });
+
+    let weak_macs = macs.iter().filter_map(|mac| match mac {
+        Hmac::None => Some(finding(
+            Severity::Insecure,
+            mac.as_ref(),
+            "packets would be sent without integrity protection",
+        )),
+        Hmac::HmacMd5 | Hmac::HmacMd5ETM => Some(finding(
+            Severity::Weak,
+            mac.as_ref(),
+            "MD5 is cryptographically broken",
+        )),
+        Hmac::HmacSha1 | Hmac::HmacSha1ETM => {
+            Some(finding(Severity::Weak, mac.as_ref(), "SHA-1 is deprecated"))
+        }
+        _ => None,
+    });
+
+    // CBC ciphers are only vulnerable to plaintext-recovery when the MAC is computed
+    // over the plaintext, which is what encrypt-and-MAC algorithms do.
+    let cbc = ciphers
+        .iter()
+        .filter(|cipher| cipher.as_ref().ends_with("-cbc"))
+        .flat_map(|cipher| {
+            macs.iter()
+                .filter(|mac| !matches!(mac, Hmac::None) && !mac.as_ref().contains("-etm@"))
+                .map(move |mac| {
+                    finding(
+                        Severity::Weak,
+                        &format!("{} + {}", cipher.as_ref(), mac.as_ref()),
+                        "CBC with encrypt-and-MAC is vulnerable to plaintext-recovery attacks",
+                    )
+                })
+        });
+
+    empty
+        .chain(weak_keys)
+        .chain(weak_ciphers)
+        .chain(weak_macs)
+        .chain(cbc)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::side::{client, server};
+
+    #[rstest]
+    #[case(Policy::Modern)]
+    #[case(Policy::Compatible)]
+    #[case(Policy::Fips)]
+    fn it_has_no_weakness_in_presets(#[case] policy: Policy) {
+        assert_eq!(server::Algorithms::from(policy).validate().unwrap(), []);
+        assert_eq!(client::Algorithms::from(policy).validate().unwrap(), []);
+    }
+
+    #[rstest]
+    #[case(Policy::Modern, false)]
+    #[case(Policy::Compatible, true)]
+    #[case(Policy::Fips, false)]
+    #[case(Policy::Legacy, true)]
+    fn it_restricts_preauth_compression(#[case] policy: Policy, #[case] zlib: bool) {
+        assert_eq!(policy.compressions().contains(&Compress::Zlib), zlib);
+        assert!(policy.compressions().contains(&Compress::ZlibOpenssh));
+    }
+
+    #[test]
+    fn it_warns_about_legacy() {
+        let findings = server::Algorithms::from(Policy::Legacy).validate().unwrap();
+
+        for algorithm in ["ssh-rsa", "ssh-dss", "3des-cbc", "hmac-md5", "hmac-sha1"] {
+            assert!(findings
+                .iter()
+                .any(|finding| finding.algorithm == algorithm));
+        }
+        assert!(findings
+            .iter()
+            .any(|finding| finding.algorithm == "aes128-cbc + hmac-sha2-256"));
+        assert!(!findings
+            .iter()
+            .any(|finding| finding.algorithm == "aes128-cbc + hmac-sha2-256-etm@openssh.com"));
+    }
+
+    #[rstest]
+    #[case(client::Algorithms { ciphers: vec![Cipher::Aes128Ctr, Cipher::None], ..Default::default() })]
+    #[case(client::Algorithms { macs: vec![Hmac::None], ..Default::default() })]
+    #[case(client::Algorithms { kexs: vec![], ..Default::default() })]
+    fn it_rejects_insecure(#[case] algorithms: client::Algorithms) {
+        assert!(matches!(
+            algorithms.validate(),
+            Err(Error::InsecureAlgorithms(findings)) if !findings.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+//! Reporting of the algorithms negociation between both sides of the session.
+
+use std::str::FromStr;
+
+use ssh_packet::{arch::NameList, trans::KexInit};
+
+use super::{Cipher, Compress, Hmac, Kex, Key};
+use crate::error::Advertised;
+
+/// The outcome of the negociation for a single category of algorithms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The algorithm negociated between both sides.
+    Negotiated(String),
+
+    /// The preferred common algorithm is not supported by this implementation.
+    Unsupported(String),
+
+    /// No algorithm in common between both sides.
+    NoCommon(Advertised),
+}
+
+impl Outcome {
+    fn new<T: FromStr>(client: &NameList, server: &NameList) -> Self {
+        match client.preferred_in(server) {
+            Some(algorithm) if algorithm.parse::<T>().is_ok() => Self::Negotiated(algorithm.into()),
+            Some(algorithm) => Self::Unsupported(algorithm.into()),
+            None => Self::NoCommon(Advertised::new(client, server)),
+        }
+    }
+
+    /// Whether an algorithm has successfully been negociated.
+    pub fn is_negotiated(&self) -> bool {
+        matches!(self, Self::Negotiated(_))
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Negotiated(algorithm) => write!(f, "`{algorithm}`"),
+            Self::Unsupported(algorithm) => write!(f, "`{algorithm}` is unsupported"),
+            Self::NoCommon(advertised) => write!(f, "no algorithm in common, {advertised}"),
+        }
+    }
+}
+
+/// A report of the algorithms negociation, listing both [`KexInit`] offers
+/// and the [`Outcome`] for each category of algorithms.
+///
+/// The directional categories are `(client-to-server, server-to-client)` tuples.
+#[derive(Debug, Clone)]
+pub struct NegotiationReport {
+    /// The [`KexInit`] offered by the _client_.
+    pub client: KexInit,
+
+    /// The [`KexInit`] offered by the _server_.
+    pub server: KexInit,
+
+    /// The outcome for the _key-exchange_ algorithms.
+    pub kex: Outcome,
+
+    /// The outcome for the _server host key_ algorithms.
+    pub key: Outcome,
+
+    /// The outcomes for the _encryption_ algorithms.
+    pub cipher: (Outcome, Outcome),
+
+    /// The outcomes for the _hmac_ algorithms.
+    pub mac: (Outcome, Outcome),
+
+    /// The outcomes for the _compression_ algorithms.
+    pub compression: (Outcome, Outcome),
+}
+
+impl NegotiationReport {
+    /// Negociate each category of algorithms between the `client` and `server` offers.
+    pub fn new(client: &KexInit, server: &KexInit) -> Self {
+        Self {
+            kex: Outcome::new::<Kex>(&client.kex_algorithms, &server.kex_algorithms),
+            key: Outcome::new::<Key>(
+                &client.server_host_key_algorithms,
+                &server.server_host_key_algorithms,
+            ),
+            cipher: (
+                Outcome::new::<Cipher>(
+                    &client.encryption_algorithms_client_to_server,
+                    &server.encryption_algorithms_client_to_server,
+                ),
+                Outcome::new::<Cipher>(
+                    &client.encryption_algorithms_server_to_client,
+                    &server.encryption_algorithms_server_to_client,
+                ),
+            ),
+            mac: (
+                Outcome::new::<Hmac>(
+                    &client.mac_algorithms_client_to_server,
+                    &server.mac_algorithms_client_to_server,
+                ),
+                Outcome::new::<Hmac>(
+                    &client.mac_algorithms_server_to_client,
+                    &server.mac_algorithms_server_to_client,
+                ),
+            ),
+            compression: (
+                Outcome::new::<Compress>(
+                    &client.compression_algorithms_client_to_server,
+                    &server.compression_algorithms_client_to_server,
+                ),
+                Outcome::new::<Compress>(
+                    &client.compression_algorithms_server_to_client,
+                    &server.compression_algorithms_server_to_client,
+                ),
+            ),
+            client: client.clone(),
+            server: server.clone(),
+        }
+    }
+
+    /// Iterate over the outcome of each category, along with it's name.
+    pub fn outcomes(&self) -> impl Iterator<Item = (&'static str, &Outcome)> {
+        [
+            ("kex", &self.kex),
+            ("host key", &self.key),
+            ("cipher (client-to-server)", &self.cipher.0),
+            ("cipher (server-to-client)", &self.cipher.1),
+            ("mac (client-to-server)", &self.mac.0),
+            ("mac (server-to-client)", &self.mac.1),
+            ("compression (client-to-server)", &self.compression.0),
+            ("compression (server-to-client)", &self.compression.1),
+        ]
+        .into_iter()
+    }
+
+    /// Iterate over the categories that failed to be negociated.
+    pub fn failures(&self) -> impl Iterator<Item = (&'static str, &Outcome)> {
+        self.outcomes()
+            .filter(|(_, outcome)| !outcome.is_negotiated())
+    }
+}
+
+impl std::fmt::Display for NegotiationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (category, outcome)) in self.outcomes().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{category}: {outcome}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kexinit(ciphers: &[&str], keys: &[&str]) -> KexInit {
+        KexInit {
+            cookie: Default::default(),
+            kex_algorithms: NameList::new(["curve25519-sha256"]),
+            server_host_key_algorithms: NameList::new(keys),
+            encryption_algorithms_client_to_server: NameList::new(ciphers),
+            encryption_algorithms_server_to_client: NameList::new(["aes256-ctr"]),
+            mac_algorithms_client_to_server: NameList::new(["hmac-sha2-256"]),
+            mac_algorithms_server_to_client: NameList::new(["hmac-sha2-256"]),
+            compression_algorithms_client_to_server: NameList::new(["none"]),
+            compression_algorithms_server_to_client: NameList::new(["none"]),
+            languages_client_to_server: Default::default(),
+            languages_server_to_client: Default::default(),
+            first_kex_packet_follows: false.into(),
+        }
+    }
+
+    #[test]
+    fn it_reports_each_category() {
+        let report = NegotiationReport::new(
+            &kexinit(&["aes128-ctr", "aes256-ctr"], &["ssh-ed25519"]),
+            &kexinit(&["aes256-cbc"], &["ssh-ed25519"]),
+        );
+
+        assert_eq!(report.kex, Outcome::Negotiated("curve25519-sha256".into()));
+        assert_eq!(
+            report.cipher.0,
+            Outcome::NoCommon(Advertised {
+                client: vec!["aes128-ctr".into(), "aes256-ctr".into()],
+                server: vec!["aes256-cbc".into()],
+            })
+        );
+        assert_eq!(report.cipher.1, Outcome::Negotiated("aes256-ctr".into()));
+        assert_eq!(
+            report
+                .failures()
+                .map(|(category, _)| category)
+                .collect::<Vec<_>>(),
+            ["cipher (client-to-server)"]
+        );
+    }
+
+    #[test]
+    fn it_reports_unsupported_algorithms() {
+        let report = NegotiationReport::new(
+            &kexinit(&["aes256-ctr"], &["ssh-unknown"]),
+            &kexinit(&["aes256-ctr"], &["ssh-unknown"]),
+        );
+
+        assert_eq!(report.key, Outcome::Unsupported("ssh-unknown".into()));
+    }
+}
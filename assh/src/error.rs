@@ -1,8 +1,10 @@
 //! Collection of error handling types and aliases.
 
-use ssh_packet::trans;
+use ssh_packet::{arch::NameList, trans};
 use thiserror::Error;
 
+use crate::algorithm::NegotiationReport;
+
 /// The disconnection side for [`DisconnectedError`].
 #[derive(Debug, Clone)]
 pub enum DisconnectedBy {
@@ -28,6 +30,37 @@ pub struct DisconnectedError {
     pub description: String,
 }
 
+/// The algorithms advertised by both sides for a category that failed to be negociated,
+/// see the [`NegotiationReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advertised {
+    /// The algorithms advertised by the _client_, in order of preference.
+    pub client: Vec<String>,
+
+    /// The algorithms advertised by the _server_.
+    pub server: Vec<String>,
+}
+
+impl Advertised {
+    pub(crate) fn new(client: &NameList, server: &NameList) -> Self {
+        Self {
+            client: client.into_iter().map(Into::into).collect(),
+            server: server.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Advertised {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "client advertised `{}`, server advertised `{}`",
+            self.client.join(","),
+            self.server.join(",")
+        )
+    }
+}
+
 /// The error types that can occur when manipulating this crate.
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -57,24 +90,37 @@ pub enum Error {
     Signature(#[from] signature::Error),
 
     /// No common kex algorithm found between both sides.
-    #[error("Unable to negociate a common kex algorithm")]
-    NoCommonKex,
+    #[error("Unable to negociate a common kex algorithm:\n{0}")]
+    NoCommonKex(Box<NegotiationReport>),
 
     /// No common key algorithm found between both sides.
-    #[error("Unable to negociate a common host key algorithm")]
-    NoCommonKey,
+    #[error("Unable to negociate a common host key algorithm:\n{0}")]
+    NoCommonKey(Box<NegotiationReport>),
 
     /// No common cipher algorithm found between both sides.
-    #[error("Unable to negociate a common encryption algorithm")]
-    NoCommonCipher,
+    #[error("Unable to negociate a common encryption algorithm:\n{0}")]
+    NoCommonCipher(Box<NegotiationReport>),
 
     /// No common hmac algorithm found between both sides.
-    #[error("Unable to negociate a common HMAC algorithm")]
-    NoCommonHmac,
+    #[error("Unable to negociate a common HMAC algorithm:\n{0}")]
+    NoCommonHmac(Box<NegotiationReport>),
 
     /// No common compression algorithm found between both sides.
-    #[error("Unable to negociate a common compression algorithm")]
-    NoCommonCompression,
+    #[error("Unable to negociate a common compression algorithm:\n{0}")]
+    NoCommonCompression(Box<NegotiationReport>),
+
+    /// The enabled algorithms contain insecure algorithms or combinations.
+    #[error("Refusing to use insecure algorithms: {0:?}")]
+    InsecureAlgorithms(Vec<crate::algorithm::policy::Finding>),
+
+    /// None of the server keys is compatible with the enabled host key algorithms,
+    /// such as a `ssh-dss` key which is only enabled by [`Policy::Legacy`](crate::algorithm::Policy::Legacy).
+    #[error("None of the server keys is compatible with the enabled host key algorithms")]
+    NoUsableHostKey,
+
+    /// The `PROXY` protocol header was malformed or unsupported.
+    #[error("Unable to parse the PROXY protocol header")]
+    ProxyHeader,
 
     /// Protocol error in the key-exchange.
     #[error("Error in the kex-exchange algorithm")]
@@ -84,6 +130,19 @@ pub enum Error {
     #[error("The cipher ended up in an error")]
     Cipher,
 
+    /// The peer exceeded one of the configured [`Limits`](crate::side::Limits).
+    #[error("Peer exceeded the limit on the {what}, {value} > {max}")]
+    LimitExceeded {
+        /// What has been limited.
+        what: &'static str,
+
+        /// The value sent by the peer.
+        value: usize,
+
+        /// The configured limit.
+        max: usize,
+    },
+
     /// The message received was unexpected in the current context.
     #[error("Peer sent a message that made no sense in the current context")]
     UnexpectedMessage,
@@ -93,5 +152,208 @@ pub enum Error {
     Disconnected(#[from] DisconnectedError),
 }
 
+impl Error {
+    /// The [`trans::DisconnectReason`] of the disconnection, if the session has been disconnected.
+    pub fn disconnect_reason(&self) -> Option<&trans::DisconnectReason> {
+        match self {
+            Self::Disconnected(DisconnectedError { reason, .. }) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// The [`NegotiationReport`] of the key-exchange, if the algorithms failed to be negociated.
+    pub fn negotiation_report(&self) -> Option<&NegotiationReport> {
+        match self {
+            Self::NoCommonKex(report)
+            | Self::NoCommonKey(report)
+            | Self::NoCommonCipher(report)
+            | Self::NoCommonHmac(report)
+            | Self::NoCommonCompression(report) => Some(report),
+            _ => None,
+        }
+    }
+
+    /// Whether the error is transient, in which case retrying with a new session may succeed,
+    /// such as network failures, timeouts, or the peer being temporarily unavailable,
+    /// a disconnection [`trans::DisconnectReason::ByApplication`] being a deliberate decision instead.
+    pub fn is_transient(&self) -> bool {
+        use std::io::ErrorKind;
+
+        match self {
+            Self::Io(err) => matches!(
+                err.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::NotConnected
+            ),
+            Self::Disconnected(DisconnectedError { reason, .. }) => matches!(
+                reason,
+                trans::DisconnectReason::ConnectionLost
+                    | trans::DisconnectReason::TooManyConnections
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the error results of the peer violating the protocol,
+    /// including when we disconnected the peer for such a reason.
+    pub fn is_protocol_violation(&self) -> bool {
+        match self {
+            Self::Id(_)
+            | Self::Binary(_)
+            | Self::Integrity(_)
+            | Self::Signature(_)
+            | Self::ProxyHeader
+            | Self::KexError
+            | Self::Cipher
+            | Self::LimitExceeded { .. }
+            | Self::UnexpectedMessage => true,
+            Self::Disconnected(DisconnectedError {
+                by: DisconnectedBy::Us,
+                reason,
+                ..
+            }) => matches!(
+                reason,
+                trans::DisconnectReason::ProtocolError
+                    | trans::DisconnectReason::MacError
+                    | trans::DisconnectReason::CompressionError
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the error results of a local misconfiguration, such as invalid keys,
+    /// insecure algorithms, or algorithms that are not supported by the peer.
+    pub fn is_misconfiguration(&self) -> bool {
+        match self {
+            Self::Key(_)
+            | Self::InsecureAlgorithms(_)
+            | Self::NoUsableHostKey
+            | Self::NoCommonKex(_)
+            | Self::NoCommonKey(_)
+            | Self::NoCommonCipher(_)
+            | Self::NoCommonHmac(_)
+            | Self::NoCommonCompression(_) => true,
+            Self::Disconnected(DisconnectedError { reason, .. }) => matches!(
+                reason,
+                trans::DisconnectReason::KeyExchangeFailed
+                    | trans::DisconnectReason::ProtocolVersionNotSupported
+                    | trans::DisconnectReason::HostKeyNotVerifiable
+                    | trans::DisconnectReason::ServiceNotAvailable
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the session has been disconnected after exhausting the authentication methods or attempts.
+    pub fn is_auth_exhausted(&self) -> bool {
+        matches!(
+            self.disconnect_reason(),
+            Some(
+                trans::DisconnectReason::NoMoreAuthMethodsAvailable
+                    | trans::DisconnectReason::AuthCancelledByUser
+                    | trans::DisconnectReason::IllegalUserName
+            )
+        )
+    }
+}
+
 /// A handy [`std::result::Result`] type alias bounding the [`enum@Error`] struct as `E`.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use ssh_packet::trans::DisconnectReason;
+
+    use super::*;
+
+    fn disconnected(by: DisconnectedBy, reason: DisconnectReason) -> Error {
+        DisconnectedError {
+            by,
+            reason,
+            description: Default::default(),
+        }
+        .into()
+    }
+
+    #[rstest]
+    #[case(std::io::Error::from(std::io::ErrorKind::TimedOut).into(), true, false, false)]
+    #[case(
+        disconnected(DisconnectedBy::Them, DisconnectReason::ConnectionLost),
+        true,
+        false,
+        false
+    )]
+    #[case(
+        disconnected(DisconnectedBy::Them, DisconnectReason::ByApplication),
+        false,
+        false,
+        false
+    )]
+    #[case(Error::UnexpectedMessage, false, true, false)]
+    #[case(
+        disconnected(DisconnectedBy::Us, DisconnectReason::MacError),
+        false,
+        true,
+        false
+    )]
+    #[case(
+        disconnected(DisconnectedBy::Them, DisconnectReason::ProtocolError),
+        false,
+        false,
+        false
+    )]
+    #[case(Error::InsecureAlgorithms(vec![]), false, false, true)]
+    #[case(Error::NoUsableHostKey, false, false, true)]
+    #[case(
+        disconnected(DisconnectedBy::Us, DisconnectReason::KeyExchangeFailed),
+        false,
+        false,
+        true
+    )]
+    fn it_classifies_errors(
+        #[case] err: Error,
+        #[case] transient: bool,
+        #[case] protocol_violation: bool,
+        #[case] misconfiguration: bool,
+    ) {
+        assert_eq!(err.is_transient(), transient);
+        assert_eq!(err.is_protocol_violation(), protocol_violation);
+        assert_eq!(err.is_misconfiguration(), misconfiguration);
+        assert!(!err.is_auth_exhausted());
+    }
+
+    #[test]
+    fn it_detects_auth_exhaustion() {
+        let err = disconnected(
+            DisconnectedBy::Them,
+            DisconnectReason::NoMoreAuthMethodsAvailable,
+        );
+
+        assert!(err.is_auth_exhausted());
+        assert!(matches!(
+            err.disconnect_reason(),
+            Some(DisconnectReason::NoMoreAuthMethodsAvailable)
+        ));
+    }
+
+    #[test]
+    fn it_displays_both_sides_algorithms() {
+        let advertised = Advertised::new(
+            &NameList::new(["hmac-sha2-512", "hmac-sha2-256"]),
+            &NameList::new(["hmac-sha1"]),
+        );
+
+        assert_eq!(
+            advertised.to_string(),
+            "client advertised `hmac-sha2-512,hmac-sha2-256`, server advertised `hmac-sha1`"
+        );
+    }
+}
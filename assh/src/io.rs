@@ -0,0 +1,88 @@
+//! Adapters to use I/O primitives from other runtimes with the [`Session`](crate::Session).
+
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+/// An adapter exposing a [`tokio`] stream as an [`AsyncBufRead`] + [`AsyncWrite`] stream.
+///
+/// Reads are buffered, while writes are not since the session already writes
+/// whole packets at once, removing the need for any additional buffering.
+#[derive(Debug)]
+pub struct Tokio<T> {
+    inner: tokio::io::BufReader<T>,
+}
+
+impl<T> Tokio<T>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    /// Wrap the `stream` in the adapter.
+    pub fn new(stream: T) -> Self {
+        Self {
+            inner: tokio::io::BufReader::new(stream),
+        }
+    }
+
+    /// Unwrap the adapter, discarding any buffered data.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> AsyncRead for Tokio<T>
+where
+    T: tokio::io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.inner),
+            cx,
+            &mut buf
+        ))?;
+
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+impl<T> AsyncBufRead for Tokio<T>
+where
+    T: tokio::io::AsyncRead + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        tokio::io::AsyncBufRead::poll_fill_buf(Pin::new(&mut self.get_mut().inner), cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        tokio::io::AsyncBufRead::consume(Pin::new(&mut self.inner), amt)
+    }
+}
+
+impl<T> AsyncWrite for Tokio<T>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.inner), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.inner), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.inner), cx)
+    }
+}
@@ -0,0 +1,107 @@
+//! Export of the key-exchange secrets, for debugging purposes.
+//!
+//! **Warning**: anyone with access to the exported secrets is able to decrypt
+//! the whole session, this must never be enabled outside of a lab environment.
+//!
+//! #### Format
+//!
+//! Each key-exchange (and re-exchange) produces an [`Entry`], which is displayed
+//! as the following lines, in a way similar to the `SSLKEYLOGFILE` format:
+//!
+//! ```text
+//! <LABEL> <SESSION_ID> <VALUE>
+//! ```
+//!
+//! where `<SESSION_ID>` is the hex-encoded session identifier, stable across re-exchanges,
+//! and `<LABEL>` is one of the following:
+//!
+//! - `EXCHANGE_HASH`: the hex-encoded exchange hash `H`.
+//! - `SHARED_SECRET`: the hex-encoded shared secret `K`, as encoded in the `mpint`, without it's length prefix.
+//! - `CLIENT_TO_SERVER_CIPHER` & `SERVER_TO_CLIENT_CIPHER`: the name of the negociated cipher for the direction.
+//! - `CLIENT_TO_SERVER_MAC` & `SERVER_TO_CLIENT_MAC`: the name of the negociated MAC for the direction.
+//! - `CLIENT_TO_SERVER_IV` & `SERVER_TO_CLIENT_IV`: the hex-encoded derived initial IV for the direction.
+//! - `CLIENT_TO_SERVER_KEY` & `SERVER_TO_CLIENT_KEY`: the hex-encoded derived encryption key for the direction.
+//! - `CLIENT_TO_SERVER_MAC_KEY` & `SERVER_TO_CLIENT_MAC_KEY`: the hex-encoded derived integrity key for the direction.
+
+use crate::algorithm::{Cipher, Hmac};
+
+/// A hook receiving the secrets of each key-exchange of the session.
+pub trait KeyLog: Send + Sync {
+    /// Process the secrets of the key-exchange.
+    fn log(&mut self, entry: &Entry<'_>);
+}
+
+impl<T: FnMut(&Entry<'_>) + Send + Sync> KeyLog for T {
+    fn log(&mut self, entry: &Entry<'_>) {
+        (self)(entry)
+    }
+}
+
+/// The secrets derived from a single key-exchange.
+#[derive(Debug)]
+pub struct Entry<'e> {
+    /// The session identifier, derived from the first key-exchange.
+    pub session_id: &'e [u8],
+
+    /// The exchange hash `H` of this key-exchange.
+    pub exchange_hash: &'e [u8],
+
+    /// The shared secret `K` of this key-exchange.
+    pub shared_secret: &'e [u8],
+
+    /// The keys used from the client to the server.
+    pub client_to_server: Direction<'e>,
+
+    /// The keys used from the server to the client.
+    pub server_to_client: Direction<'e>,
+}
+
+/// The algorithms and keys negociated for a single direction.
+#[derive(Debug)]
+pub struct Direction<'e> {
+    /// The negociated cipher algorithm.
+    pub cipher: &'e Cipher,
+
+    /// The negociated MAC algorithm.
+    pub hmac: &'e Hmac,
+
+    /// The derived initial IV.
+    pub iv: &'e [u8],
+
+    /// The derived encryption key.
+    pub key: &'e [u8],
+
+    /// The derived integrity key.
+    pub mac_key: &'e [u8],
+}
+
+struct Hex<'h>(&'h [u8]);
+
+impl std::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl std::fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = Hex(self.session_id);
+
+        writeln!(f, "EXCHANGE_HASH {id} {}", Hex(self.exchange_hash))?;
+        write!(f, "SHARED_SECRET {id} {}", Hex(self.shared_secret))?;
+
+        for (label, direction) in [
+            ("CLIENT_TO_SERVER", &self.client_to_server),
+            ("SERVER_TO_CLIENT", &self.server_to_client),
+        ] {
+            writeln!(f)?;
+            writeln!(f, "{label}_CIPHER {id} {}", direction.cipher.as_ref())?;
+            writeln!(f, "{label}_MAC {id} {}", direction.hmac.as_ref())?;
+            writeln!(f, "{label}_IV {id} {}", Hex(direction.iv))?;
+            writeln!(f, "{label}_KEY {id} {}", Hex(direction.key))?;
+            write!(f, "{label}_MAC_KEY {id} {}", Hex(direction.mac_key))?;
+        }
+
+        Ok(())
+    }
+}
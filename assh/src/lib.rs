@@ -26,11 +26,20 @@
 mod stream;
 
 pub mod algorithm;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod io;
+pub mod keylog;
+pub mod proxy;
+pub mod quirks;
 pub mod service;
 pub mod side;
 
 pub mod error;
 pub use error::{Error, Result};
 
+mod rng;
+pub use rng::Rng;
+
 mod session;
-pub use session::Session;
+pub use session::{Session, TrafficShaping};
@@ -0,0 +1,211 @@
+//! Support for the HAProxy [`PROXY`] protocol, versions 1 and 2.
+//!
+//! When enabled on the [`Server`](crate::side::server::Server),
+//! the header is parsed before the version exchange, to recover
+//! the addresses of the original connection behind a load-balancer.
+//!
+//! [`PROXY`]: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+use crate::{Error, Result};
+
+/// The signature prefixing the binary version 2 of the protocol.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum size of a version 1 header, including the `\r\n`.
+const V1_MAX_SIZE: usize = 107;
+
+/// The addresses of the original connection, as forwarded by the proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addresses {
+    /// The address of the peer that initiated the connection.
+    pub source: SocketAddr,
+
+    /// The address the peer initially connected to.
+    pub destination: SocketAddr,
+}
+
+/// Read a `PROXY` header in either version from the provided `reader`.
+///
+/// Returns [`None`] when the header is valid but doesn't carry any addresses,
+/// as in `UNKNOWN` or `LOCAL` connections (i.e. health-checks).
+pub async fn read(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Addresses>> {
+    let mut prefix = [0u8; V2_SIGNATURE.len()];
+    reader.read_exact(&mut prefix).await?;
+
+    if &prefix == V2_SIGNATURE {
+        read_v2(reader).await
+    } else if prefix.starts_with(b"PROXY ") {
+        let mut line = prefix.to_vec();
+        reader
+            .take((V1_MAX_SIZE - prefix.len()) as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+
+        parse_v1(&line)
+    } else {
+        Err(Error::ProxyHeader)
+    }
+}
+
+fn parse_v1(line: &[u8]) -> Result<Option<Addresses>> {
+    let line = std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.strip_suffix("\r\n"))
+        .ok_or(Error::ProxyHeader)?;
+
+    let mut fields = line.split(' ').skip(1);
+
+    match fields.next() {
+        Some("UNKNOWN") => Ok(None),
+        Some(family @ ("TCP4" | "TCP6")) => {
+            let (Some(src), Some(dst), Some(sport), Some(dport), None) = (
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+            ) else {
+                return Err(Error::ProxyHeader);
+            };
+
+            let (src, dst): (IpAddr, IpAddr) = match family {
+                "TCP4" => (
+                    src.parse::<Ipv4Addr>()
+                        .map_err(|_| Error::ProxyHeader)?
+                        .into(),
+                    dst.parse::<Ipv4Addr>()
+                        .map_err(|_| Error::ProxyHeader)?
+                        .into(),
+                ),
+                _ => (
+                    src.parse::<Ipv6Addr>()
+                        .map_err(|_| Error::ProxyHeader)?
+                        .into(),
+                    dst.parse::<Ipv6Addr>()
+                        .map_err(|_| Error::ProxyHeader)?
+                        .into(),
+                ),
+            };
+
+            Ok(Some(Addresses {
+                source: SocketAddr::new(src, sport.parse().map_err(|_| Error::ProxyHeader)?),
+                destination: SocketAddr::new(dst, dport.parse().map_err(|_| Error::ProxyHeader)?),
+            }))
+        }
+        _ => Err(Error::ProxyHeader),
+    }
+}
+
+async fn read_v2(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Addresses>> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header).await?;
+
+    let [version_command, family, len @ ..] = header;
+    let len = u16::from_be_bytes(len) as usize;
+
+    // Read the whole address block, including any trailing TLVs we don't care about.
+    let mut block = vec![0u8; len];
+    reader.read_exact(&mut block).await?;
+
+    match version_command {
+        // LOCAL: the connection was established by the proxy itself.
+        0x20 => Ok(None),
+
+        // PROXY: the connection was relayed on behalf of another peer.
+        0x21 => match family {
+            // TCP over IPv4.
+            0x11 if len >= 12 => {
+                let src = Ipv4Addr::from(
+                    <[u8; 4]>::try_from(&block[0..4]).map_err(|_| Error::ProxyHeader)?,
+                );
+                let dst = Ipv4Addr::from(
+                    <[u8; 4]>::try_from(&block[4..8]).map_err(|_| Error::ProxyHeader)?,
+                );
+                let sport = u16::from_be_bytes([block[8], block[9]]);
+                let dport = u16::from_be_bytes([block[10], block[11]]);
+
+                Ok(Some(Addresses {
+                    source: SocketAddr::new(src.into(), sport),
+                    destination: SocketAddr::new(dst.into(), dport),
+                }))
+            }
+
+            // TCP over IPv6.
+            0x21 if len >= 36 => {
+                let src = Ipv6Addr::from(
+                    <[u8; 16]>::try_from(&block[0..16]).map_err(|_| Error::ProxyHeader)?,
+                );
+                let dst = Ipv6Addr::from(
+                    <[u8; 16]>::try_from(&block[16..32]).map_err(|_| Error::ProxyHeader)?,
+                );
+                let sport = u16::from_be_bytes([block[32], block[33]]);
+                let dport = u16::from_be_bytes([block[34], block[35]]);
+
+                Ok(Some(Addresses {
+                    source: SocketAddr::new(src.into(), sport),
+                    destination: SocketAddr::new(dst.into(), dport),
+                }))
+            }
+
+            // Unspecified, UDP or UNIX sockets carry no usable addresses for us.
+            0x00 | 0x12 | 0x22 | 0x31 | 0x32 => Ok(None),
+
+            _ => Err(Error::ProxyHeader),
+        },
+
+        _ => Err(Error::ProxyHeader),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use futures::io::Cursor;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nSSH-2.0-", Some(("192.168.0.1:56324", "192.168.0.11:443")))]
+    #[case(b"PROXY TCP6 ::1 2001:db8::1 56324 22\r\nSSH-2.0-", Some(("[::1]:56324", "[2001:db8::1]:22")))]
+    #[case(b"PROXY UNKNOWN\r\nSSH-2.0-", None)]
+    #[case(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\nSSH-2.0-", None)]
+    #[case(
+        b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c\x7f\x00\x00\x01\x0a\x00\x00\x02\x30\x39\x00\x16SSH-2.0-",
+        Some(("127.0.0.1:12345", "10.0.0.2:22"))
+    )]
+    #[case(b"\r\n\r\n\0\r\nQUIT\n\x20\x00\x00\x00SSH-2.0-", None)]
+    async fn it_parses_valid(#[case] bytes: &[u8], #[case] expected: Option<(&str, &str)>) {
+        let mut reader = Cursor::new(bytes);
+
+        assert_eq!(
+            read(&mut reader).await.unwrap(),
+            expected.map(|(source, destination)| Addresses {
+                source: source.parse().unwrap(),
+                destination: destination.parse().unwrap(),
+            })
+        );
+
+        // The reader must stop right before the version exchange.
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"SSH-2.0-");
+    }
+
+    #[rstest]
+    #[case(b"SSH-2.0-billsSSH_3.6.3q3\r\n")]
+    #[case(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324\r\n")]
+    #[case(b"PROXY TCP4 ::1 ::1 56324 22\r\n")]
+    #[case(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\n")]
+    #[case(b"PROXY UDP4 192.168.0.1 192.168.0.11 56324 443\r\n")]
+    #[case(b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x04\x7f\x00\x00\x01")]
+    #[case(b"\r\n\r\n\0\r\nQUIT\n\x13\x11\x00\x00")]
+    async fn it_rejects_invalid(#[case] bytes: &[u8]) {
+        read(&mut Cursor::new(bytes)).await.unwrap_err();
+    }
+}
@@ -0,0 +1,245 @@
+//! Fingerprinting of the peer's software, and workarounds for known incompatibilities.
+//!
+//! Unless disabled in the [`Client`](crate::side::client::Client) or
+//! [`Server`](crate::side::server::Server) configuration, the [`Quirks`]
+//! of the peer are applied to our [`KexInit`] before the key-exchange.
+
+use ssh_packet::{arch::NameList, trans::KexInit, Id};
+
+/// The software of the peer, as advertised in the `softwareversion` of it's [`Id`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Software {
+    /// OpenSSH, in example `OpenSSH_9.6p1`.
+    OpenSsh {
+        /// The major version.
+        major: u32,
+
+        /// The minor version.
+        minor: u32,
+    },
+
+    /// Dropbear, in example `dropbear_2022.83`.
+    Dropbear {
+        /// The release year.
+        year: u32,
+
+        /// The release number within the year.
+        release: u32,
+    },
+
+    /// PuTTY, in example `PuTTY_Release_0.80`, with no version for development snapshots.
+    Putty {
+        /// The release version, as `(major, minor)`.
+        version: Option<(u32, u32)>,
+    },
+
+    /// The Cisco IOS implementation, in example `Cisco-1.25`.
+    Cisco,
+
+    /// Any other or unparseable software.
+    Unknown,
+}
+
+impl Software {
+    /// Fingerprint the software from the peer's [`Id`].
+    pub fn parse(id: &Id) -> Self {
+        fn version(version: &str, separator: char) -> Option<(u32, u32)> {
+            let (major, rest) = version.split_once(separator)?;
+            let minor = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(rest, |end| &rest[..end]);
+
+            Some((major.parse().ok()?, minor.parse().ok()?))
+        }
+
+        let software = id.softwareversion.as_str();
+
+        if let Some(rest) = software.strip_prefix("OpenSSH_") {
+            version(rest, '.')
+                .map(|(major, minor)| Self::OpenSsh { major, minor })
+                .unwrap_or(Self::Unknown)
+        } else if let Some(rest) = software.strip_prefix("dropbear_") {
+            version(rest, '.')
+                .map(|(year, release)| Self::Dropbear { year, release })
+                .unwrap_or(Self::Unknown)
+        } else if let Some(rest) = software.strip_prefix("PuTTY_") {
+            Self::Putty {
+                version: rest
+                    .strip_prefix("Release_")
+                    .and_then(|rest| version(rest, '.')),
+            }
+        } else if software.starts_with("Cisco-") {
+            Self::Cisco
+        } else {
+            Self::Unknown
+        }
+    }
+
+    /// The known [`Quirks`] of the software.
+    pub fn quirks(&self) -> Quirks {
+        match *self {
+            Self::OpenSsh { major, minor } => Quirks {
+                // `rsa-sha2-*` signatures were introduced in OpenSSH 7.2,
+                // see https://www.openssh.com/txt/release-7.2.
+                rsa_sha1_only: (major, minor) < (7, 2),
+                ..Default::default()
+            },
+            Self::Dropbear { year, release } => Quirks {
+                // `rsa-sha2-256` signatures were introduced in Dropbear 2020.79,
+                // see https://matt.ucc.asn.au/dropbear/CHANGES.
+                rsa_sha1_only: (year, release) < (2020, 79),
+                ..Default::default()
+            },
+            Self::Putty { version } => Quirks {
+                // `rsa-sha2-*` signatures were introduced in PuTTY 0.75,
+                // see https://www.chiark.greenend.org.uk/~sgtatham/putty/changes.html.
+                rsa_sha1_only: version.is_some_and(|version| version < (0, 75)),
+                ..Default::default()
+            },
+            // No documented workaround for Cisco yet, it's only fingerprinted.
+            Self::Cisco | Self::Unknown => Quirks::default(),
+        }
+    }
+}
+
+/// Workarounds to apply for a specific peer [`Software`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Quirks {
+    /// The peer only supports `ssh-rsa` signatures over SHA-1 for RSA keys,
+    /// so it is enabled whenever any of the `rsa-sha2-*` algorithms is,
+    /// but only when explicitly opted-in with the `rsa_sha1_fallback` field of the
+    /// [`Client`](crate::side::client::Client) or [`Server`](crate::side::server::Server)
+    /// configuration, to never downgrade the enabled host key algorithms otherwise.
+    pub rsa_sha1_only: bool,
+
+    /// The peer advertises but mishandles the encrypt-then-MAC algorithms.
+    pub no_etm: bool,
+
+    /// The peer advertises but mishandles the `hmac-sha2-512` algorithms.
+    pub no_hmac_sha2_512: bool,
+}
+
+impl Quirks {
+    /// Adjust the algorithms of our [`KexInit`] to work around the peer's quirks,
+    /// falling back to `ssh-rsa` for [`Self::rsa_sha1_only`] peers if `rsa_sha1_fallback` is set.
+    pub fn adjust(&self, kexinit: &mut KexInit, rsa_sha1_fallback: bool) {
+        if self.rsa_sha1_only && rsa_sha1_fallback {
+            let keys = &kexinit.server_host_key_algorithms;
+
+            if keys.into_iter().any(|key| key.starts_with("rsa-sha2-"))
+                && !keys.into_iter().any(|key| key == "ssh-rsa")
+            {
+                kexinit.server_host_key_algorithms =
+                    NameList::new(keys.into_iter().chain(["ssh-rsa"]));
+            }
+        }
+
+        let mac = |name: &&str| {
+            !(self.no_etm && name.ends_with("-etm@openssh.com")
+                || self.no_hmac_sha2_512 && name.starts_with("hmac-sha2-512"))
+        };
+
+        kexinit.mac_algorithms_client_to_server = NameList::new(
+            kexinit
+                .mac_algorithms_client_to_server
+                .into_iter()
+                .filter(mac),
+        );
+        kexinit.mac_algorithms_server_to_client = NameList::new(
+            kexinit
+                .mac_algorithms_server_to_client
+                .into_iter()
+                .filter(mac),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("OpenSSH_9.6p1", Software::OpenSsh { major: 9, minor: 6 })]
+    #[case("OpenSSH_7.1", Software::OpenSsh { major: 7, minor: 1 })]
+    #[case("dropbear_2022.83", Software::Dropbear { year: 2022, release: 83 })]
+    #[case("dropbear", Software::Unknown)]
+    #[case("PuTTY_Release_0.80", Software::Putty { version: Some((0, 80)) })]
+    #[case("PuTTY_Snapshot_2023-01-01.a1b2c3", Software::Putty { version: None })]
+    #[case("Cisco-1.25", Software::Cisco)]
+    #[case("billsSSH_3.6.3q3", Software::Unknown)]
+    fn it_parses_software(#[case] softwareversion: &str, #[case] expected: Software) {
+        assert_eq!(
+            Software::parse(&Id::v2(softwareversion, None::<&str>)),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("OpenSSH_9.6p1", true, "rsa-sha2-512,rsa-sha2-256")]
+    #[case("OpenSSH_7.1", true, "rsa-sha2-512,rsa-sha2-256,ssh-rsa")]
+    #[case("OpenSSH_7.1", false, "rsa-sha2-512,rsa-sha2-256")]
+    #[case("dropbear_2019.78", true, "rsa-sha2-512,rsa-sha2-256,ssh-rsa")]
+    #[case("Cisco-1.25", true, "rsa-sha2-512,rsa-sha2-256")]
+    fn it_falls_back_to_ssh_rsa(
+        #[case] softwareversion: &str,
+        #[case] rsa_sha1_fallback: bool,
+        #[case] keys: &str,
+    ) {
+        let mut kexinit = kexinit("hmac-sha2-256");
+
+        Software::parse(&Id::v2(softwareversion, None::<&str>))
+            .quirks()
+            .adjust(&mut kexinit, rsa_sha1_fallback);
+
+        assert_eq!(
+            kexinit.server_host_key_algorithms,
+            NameList::new(keys.split(','))
+        );
+    }
+
+    #[rstest]
+    #[case(
+        Quirks { no_etm: true, ..Default::default() },
+        "hmac-sha2-512,hmac-sha2-256"
+    )]
+    #[case(
+        Quirks { no_hmac_sha2_512: true, ..Default::default() },
+        "hmac-sha2-256-etm@openssh.com,hmac-sha2-256"
+    )]
+    fn it_removes_macs(#[case] quirks: Quirks, #[case] macs: &str) {
+        let mut kexinit = kexinit(
+            "hmac-sha2-512-etm@openssh.com,hmac-sha2-256-etm@openssh.com,hmac-sha2-512,hmac-sha2-256",
+        );
+
+        quirks.adjust(&mut kexinit, false);
+
+        assert_eq!(
+            kexinit.mac_algorithms_client_to_server,
+            NameList::new(macs.split(','))
+        );
+        assert_eq!(
+            kexinit.mac_algorithms_server_to_client,
+            NameList::new(macs.split(','))
+        );
+    }
+
+    fn kexinit(macs: &str) -> KexInit {
+        KexInit {
+            cookie: Default::default(),
+            kex_algorithms: NameList::new(["curve25519-sha256"]),
+            server_host_key_algorithms: NameList::new(["rsa-sha2-512", "rsa-sha2-256"]),
+            encryption_algorithms_client_to_server: NameList::new(["aes256-ctr"]),
+            encryption_algorithms_server_to_client: NameList::new(["aes256-ctr"]),
+            mac_algorithms_client_to_server: NameList::new(macs.split(',')),
+            mac_algorithms_server_to_client: NameList::new(macs.split(',')),
+            compression_algorithms_client_to_server: NameList::new(["none"]),
+            compression_algorithms_server_to_client: NameList::new(["none"]),
+            languages_client_to_server: Default::default(),
+            languages_server_to_client: Default::default(),
+            first_kex_packet_follows: false.into(),
+        }
+    }
+}
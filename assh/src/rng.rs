@@ -0,0 +1,57 @@
+//! Pluggable random number generation for the session.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+
+/// A shared handle to the cryptographically secure random number generator used throughout the session,
+/// for the _key-exchange_ cookies, the ephemeral secrets and the packet padding.
+///
+/// Seeding it deterministically allows to replay a whole session byte-for-byte,
+/// which must obviously never be done outside of tests.
+#[derive(Clone)]
+pub struct Rng(Arc<Mutex<dyn RngCore + Send>>);
+
+impl Rng {
+    /// Create a new [`Rng`] from the provided cryptographically secure `rng`.
+    pub fn new(rng: impl RngCore + CryptoRng + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(rng)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, dyn RngCore + Send + 'static> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The default [`Rng`] is a [`StdRng`] seeded from the operating system's entropy.
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(StdRng::from_entropy())
+    }
+}
+
+impl std::fmt::Debug for Rng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Rng(..)")
+    }
+}
+
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        self.lock().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.lock().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.lock().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.lock().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for Rng {}
@@ -1,6 +1,8 @@
-use either::Either;
-use futures::{AsyncBufRead, AsyncWrite, AsyncWriteExt};
+use std::time::{Duration, Instant};
+
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, FutureExt as _};
 use futures_time::future::FutureExt;
+use rand::{Rng as _, RngCore};
 use ssh_packet::{
     arch::StringUtf8,
     trans::{
@@ -12,19 +14,140 @@ use ssh_packet::{
 
 use crate::{
     error::{DisconnectedBy, DisconnectedError, Error, Result},
-    service,
+    keylog, proxy, quirks, service,
     side::Side,
     stream::Stream,
 };
 
 // TODO: Handle extension negotiation described in RFC8308
 
+/// Traffic shaping settings, to obscure the timing and size of the messages with
+/// randomized `SSH_MSG_IGNORE` packets, similarly to OpenSSH's `ObscureKeystrokeTiming`.
+///
+/// # Bandwidth
+/// While idle, the session sends a chaff packet of up to [`TrafficShaping::max_length`] bytes
+/// of data every [`TrafficShaping::idle_interval`], plus the packet and MAC overhead, which is
+/// roughly 4KiB/s with the defaults, for at most [`TrafficShaping::idle_period`] after each message.
+#[derive(Debug, Clone)]
+pub struct TrafficShaping {
+    /// Average delay after which an idle session sends a chaff packet,
+    /// randomized between half and one and a half of it.
+    pub idle_interval: Duration,
+
+    /// Period after the last sent message during which an idle session keeps
+    /// sending chaff packets, after which it stays silent until the next message.
+    pub idle_period: Duration,
+
+    /// Maximum number of chaff packets sent before each message.
+    pub chaff: usize,
+
+    /// Maximum length of the random data carried by each chaff packet.
+    pub max_length: usize,
+}
+
+impl Default for TrafficShaping {
+    fn default() -> Self {
+        Self {
+            idle_interval: Duration::from_millis(20),
+            idle_period: Duration::from_secs(2),
+            chaff: 2,
+            max_length: 64,
+        }
+    }
+}
+
+type DebugCallback = Box<dyn FnMut(&str) + Send + Sync>;
+
+/// The state of the underlying stream of a [`Session`].
+enum State<IO> {
+    Connected(Stream<IO>),
+
+    /// The stream is kept after disconnection, to be retrieved with [`Session::into_inner`].
+    Disconnected(Stream<IO>, DisconnectedError),
+
+    /// The stream has been taken out of the session.
+    Released,
+}
+
+impl<IO> State<IO> {
+    fn stream(&mut self) -> Result<&mut Stream<IO>> {
+        match self {
+            Self::Connected(stream) => Ok(stream),
+            Self::Disconnected(_, err) => Err(err.clone().into()),
+            Self::Released => Err(std::io::Error::from(std::io::ErrorKind::NotConnected).into()),
+        }
+    }
+
+    fn disconnected(&mut self, err: DisconnectedError) {
+        *self = match std::mem::replace(self, Self::Released) {
+            Self::Connected(stream) | Self::Disconnected(stream, _) => {
+                Self::Disconnected(stream, err)
+            }
+            Self::Released => Self::Released,
+        };
+    }
+
+    fn release(&mut self) -> Option<Stream<IO>> {
+        match std::mem::replace(self, Self::Released) {
+            Self::Connected(stream) | Self::Disconnected(stream, _) => Some(stream),
+            Self::Released => None,
+        }
+    }
+}
+
 /// A session wrapping a `stream` to handle **key-exchange** and **[`SSH-TRANS`]** layer messages.
+///
+/// When dropped while still connected, the session silently drops the connection,
+/// unless enabled with [`Session::disconnect_on_drop`],
+/// use [`Session::close`] to gracefully shutdown the session instead.
 pub struct Session<IO, S> {
-    stream: Either<Stream<IO>, DisconnectedError>,
+    state: State<IO>,
     config: S,
 
     peer_id: Id,
+    proxy: Option<proxy::Addresses>,
+
+    shaping: Option<TrafficShaping>,
+    last_sent: Instant,
+    last_received: Instant,
+    on_debug: Option<DebugCallback>,
+
+    drop_reason: Option<DisconnectReason>,
+
+    /// Since the [`Drop`] implementation cannot require the bounds on `IO`, the best-effort
+    /// disconnect is captured as a function pointer when creating the session.
+    abort: fn(&mut Stream<IO>, &Disconnect),
+}
+
+impl<IO, S> Drop for Session<IO, S> {
+    fn drop(&mut self) {
+        if let (State::Connected(stream), Some(reason)) = (&mut self.state, &self.drop_reason) {
+            tracing::debug!("Session dropped while connected, disconnecting with `{reason:?}`");
+
+            (self.abort)(
+                stream,
+                &Disconnect {
+                    reason: reason.clone(),
+                    description: "Session dropped".into(),
+                    language: Default::default(),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+impl<T, S> Session<crate::io::Tokio<T>, S>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    S: Side,
+{
+    /// Create a new [`Session`] from a [`tokio`] stream, such as a `tokio::net::TcpStream`,
+    /// and some configuration, without the need for any compatibility layer or buffering.
+    pub async fn new_tokio(stream: T, config: S) -> Result<Self> {
+        Self::new(crate::io::Tokio::new(stream), config).await
+    }
 }
 
 impl<IO, S> Session<IO, S>
@@ -34,22 +157,60 @@ where
 {
     /// Create a new [`Session`] from a [`AsyncBufRead`] + [`AsyncWrite`] stream,
     /// and some configuration.
+    ///
+    /// The enabled algorithms are validated beforehand, logging the weak ones,
+    /// and refusing to start the session if any of them is insecure.
     pub async fn new(mut stream: IO, config: S) -> Result<Self> {
+        for finding in config.validate()? {
+            tracing::warn!("Weak algorithm enabled, {finding}");
+        }
+
+        let proxy = if config.proxy_protocol() {
+            proxy::read(&mut stream).timeout(config.timeout()).await??
+        } else {
+            None
+        };
+
         config.id().to_async_writer(&mut stream).await?;
         stream.flush().await?;
 
-        let peer_id = Id::from_async_reader(&mut stream)
+        let peer_id = read_id(&mut stream, config.limits().max_id_length)
             .timeout(config.timeout())
             .await??;
 
-        let stream = Stream::new(stream, config.timeout());
-
-        tracing::debug!("Session started with peer `{peer_id}`");
+        let stream = Stream::new(
+            stream,
+            config.timeout(),
+            config.limits().max_packet_length,
+            config.rng().clone(),
+        );
+
+        match &proxy {
+            Some(proxy::Addresses {
+                source,
+                destination,
+            }) => tracing::debug!(
+                "Session started with peer `{peer_id}`, proxied from {source} to {destination}"
+            ),
+            None => tracing::debug!("Session started with peer `{peer_id}`"),
+        }
 
         Ok(Self {
-            stream: Either::Left(stream),
+            state: State::Connected(stream),
             config,
             peer_id,
+            proxy,
+            shaping: None,
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+            on_debug: None,
+            drop_reason: None,
+            abort: |stream, message| {
+                // Only attempt to send the message if it can be done without blocking.
+                if let Some(Err(err)) = stream.send(message).now_or_never() {
+                    tracing::debug!("Unable to disconnect the dropped session: {err}");
+                }
+            },
         })
     }
 
@@ -58,21 +219,99 @@ where
         &self.peer_id
     }
 
+    /// Fingerprint the [`quirks::Software`] of the connected peer from it's [`Id`].
+    pub fn peer_software(&self) -> quirks::Software {
+        quirks::Software::parse(&self.peer_id)
+    }
+
+    /// Access the original [`proxy::Addresses`] of the connection,
+    /// if a `PROXY` protocol header was received and carried any.
+    pub fn proxy_addresses(&self) -> Option<&proxy::Addresses> {
+        self.proxy.as_ref()
+    }
+
     /// Access initial exchange hash.
     pub fn session_id(&self) -> Option<&[u8]> {
-        self.stream.as_ref().left().and_then(Stream::session_id)
+        match &self.state {
+            State::Connected(stream) | State::Disconnected(stream, _) => stream.session_id(),
+            State::Released => None,
+        }
+    }
+
+    /// Register a [`keylog::KeyLog`] hook to export the secrets of every subsequent key-exchange,
+    /// see the [`keylog`] module for the format and caveats.
+    pub fn keylog(&mut self, keylog: impl keylog::KeyLog + 'static) {
+        if let Ok(stream) = self.state.stream() {
+            stream.with_keylog(keylog);
+        }
+    }
+
+    /// Enable or disable [`TrafficShaping`] for the session, padding each sent message
+    /// and the idle periods spent in [`Session::readable`] with chaff packets.
+    pub fn traffic_shaping(&mut self, shaping: impl Into<Option<TrafficShaping>>) {
+        self.shaping = shaping.into();
+    }
+
+    /// Register a callback receiving the `SSH_MSG_DEBUG` messages the peer
+    /// flagged with `always_display`, the other ones only going to [`tracing`].
+    pub fn on_debug(&mut self, callback: impl FnMut(&str) + Send + Sync + 'static) {
+        self.on_debug = Some(Box::new(callback));
+    }
+
+    /// Set the `reason` of the disconnect message sent when the session is dropped
+    /// while still connected, or `None` to drop the connection silently (the default).
+    ///
+    /// # Note
+    /// The message is only sent if the stream is ready to accept it without blocking,
+    /// otherwise it may be partially written right before the connection is dropped.
+    pub fn disconnect_on_drop(&mut self, reason: impl Into<Option<DisconnectReason>>) {
+        self.drop_reason = reason.into();
     }
 
     /// Waits until the [`Session`] becomes readable,
     /// mainly to be used with [`Session::recv`] in [`futures::select`],
     /// since the `recv` method is **not cancel-safe**.
+    ///
+    /// # Cancel safety
+    /// This method is cancel-safe, including when it sends chaff packets for [`TrafficShaping`].
     pub async fn readable(&mut self) -> Result<()> {
-        let stream = match &mut self.stream {
-            Either::Left(stream) => stream,
-            Either::Right(err) => return Err(err.clone().into()),
+        let stream = self.state.stream()?;
+
+        stream.flush().await?;
+
+        let Some(shaping) = &self.shaping else {
+            return stream.fill_buf().await;
         };
 
-        stream.fill_buf().await
+        loop {
+            if self.last_sent.elapsed() >= shaping.idle_period {
+                break stream.fill_buf().await;
+            }
+
+            let interval = shaping.idle_interval.as_micros() as u64;
+            let delay =
+                Duration::from_micros(stream.rng().gen_range(interval / 2..=interval * 3 / 2));
+
+            let idle = futures::select_biased! {
+                res = stream.fill_buf().fuse() => Some(res),
+                _ = futures_time::task::sleep(delay.into()).fuse() => None,
+            };
+
+            match idle {
+                Some(res) => break res,
+                None if self.last_sent.elapsed() < shaping.idle_period => {
+                    Self::chaff(stream, shaping.max_length).await?
+                }
+                None => (),
+            }
+        }
+    }
+
+    async fn chaff(stream: &mut Stream<IO>, max_length: usize) -> Result<()> {
+        let mut data = vec![0; stream.rng().gen_range(0..=max_length)];
+        stream.rng().fill_bytes(&mut data);
+
+        stream.send(&Ignore { data: data.into() }).await
     }
 
     /// Receive a _packet_ from the connected peer.
@@ -81,24 +320,48 @@ where
     /// This method is **not cancel-safe**, if used within a [`futures::select`] call,
     /// some data may be partially received.
     pub async fn recv(&mut self) -> Result<Packet> {
-        loop {
-            let stream = match &mut self.stream {
-                Either::Left(stream) => stream,
-                Either::Right(err) => return Err(err.clone().into()),
-            };
+        let mut ignored = 0;
 
-            if stream.is_rekeyable() || stream.peek().await?.to::<KexInit>().is_ok() {
+        loop {
+            let stream = self.state.stream()?;
+
+            let rekey = stream.is_rekeyable()
+                || match stream.peek().await {
+                    Ok(packet) => packet.to::<KexInit>().is_ok(),
+                    Err(err) => return Err(self.violation(err).await),
+                };
+            if rekey {
                 if let Err(err) = self.config.kex(stream, &self.peer_id).await {
-                    return Err(self
-                        .disconnect(DisconnectReason::KeyExchangeFailed, err.to_string())
-                        .await
-                        .into());
+                    return Err(self.kex_failed(err).await);
                 }
 
                 continue;
             }
 
-            let packet = stream.recv().await?;
+            let packet = match stream.recv().await {
+                Ok(packet) => packet,
+                Err(err) => return Err(self.violation(err).await),
+            };
+
+            if packet.to::<Ignore>().is_ok() || packet.to::<Debug>().is_ok() {
+                ignored += 1;
+
+                let limits = self.config.limits();
+                let max = limits.max_ignored_messages.saturating_add(
+                    (self.last_received.elapsed().as_millis() as usize)
+                        .saturating_mul(limits.ignored_messages_rate)
+                        / 1000,
+                );
+                if ignored > max {
+                    return Err(self
+                        .violation(Error::LimitExceeded {
+                            what: "consecutive ignored messages",
+                            value: ignored,
+                            max,
+                        })
+                        .await);
+                }
+            }
 
             if let Ok(Disconnect {
                 reason,
@@ -108,7 +371,7 @@ where
             {
                 tracing::warn!("Peer disconnected with `{reason:?}`: {}", &*description);
 
-                self.stream = Either::Right(DisconnectedError {
+                self.state.disconnected(DisconnectedError {
                     by: DisconnectedBy::Them,
                     reason,
                     description: description.into_string(),
@@ -117,9 +380,20 @@ where
                 tracing::debug!("Received an 'ignore' message with length {}", data.len());
             } else if let Ok(Unimplemented { seq }) = packet.to() {
                 tracing::debug!("Received an 'unimplemented' message about packet #{seq}",);
-            } else if let Ok(Debug { message, .. }) = packet.to() {
+            } else if let Ok(Debug {
+                always_display,
+                message,
+                ..
+            }) = packet.to()
+            {
                 tracing::debug!("Received a 'debug' message: {}", &*message);
+
+                if let (true, Some(callback)) = (*always_display, &mut self.on_debug) {
+                    callback(&message);
+                }
             } else {
+                self.last_received = Instant::now();
+
                 break Ok(packet);
             }
         }
@@ -127,34 +401,107 @@ where
 
     /// Send a _packet_ to the connected peer.
     pub async fn send(&mut self, message: &impl ToPacket) -> Result<()> {
-        let stream = match &mut self.stream {
-            Either::Left(stream) => stream,
-            Either::Right(err) => return Err(err.clone().into()),
-        };
+        let stream = self.state.stream()?;
 
         if stream.is_rekeyable()
             || (stream.is_readable().await? && stream.peek().await?.to::<KexInit>().is_ok())
         {
             if let Err(err) = self.config.kex(stream, &self.peer_id).await {
-                return Err(self
-                    .disconnect(DisconnectReason::KeyExchangeFailed, err.to_string())
-                    .await
-                    .into());
+                return Err(self.kex_failed(err).await);
+            }
+        }
+
+        if let Some(shaping) = &self.shaping {
+            for _ in 0..stream.rng().gen_range(0..=shaping.chaff) {
+                Self::chaff(stream, shaping.max_length).await?;
             }
         }
 
+        self.last_sent = Instant::now();
+
         stream.send(message).await
     }
 
+    /// Disconnect from the peer with a protocol error if the `err` is a violation of the
+    /// [`Limits`](crate::side::Limits), returning the resulting error.
+    async fn violation(&mut self, err: Error) -> Error {
+        match err {
+            Error::LimitExceeded { .. } => self
+                .disconnect(DisconnectReason::ProtocolError, err.to_string())
+                .await
+                .into(),
+            err => err,
+        }
+    }
+
+    /// Disconnect from the peer after a failed key-exchange, returning the original
+    /// error if it carries a [`NegotiationReport`](crate::algorithm::NegotiationReport).
+    async fn kex_failed(&mut self, err: Error) -> Error {
+        let disconnected = self
+            .disconnect(DisconnectReason::KeyExchangeFailed, err.to_string())
+            .await;
+
+        if err.negotiation_report().is_some() {
+            err
+        } else {
+            disconnected.into()
+        }
+    }
+
+    /// Send an `SSH_MSG_IGNORE` message carrying `length` random bytes to the peer.
+    pub async fn ignore(&mut self, length: usize) -> Result<()> {
+        let mut data = vec![0; length];
+
+        if let Ok(stream) = self.state.stream() {
+            stream.rng().fill_bytes(&mut data);
+        }
+
+        self.send(&Ignore { data: data.into() }).await
+    }
+
+    /// Send an `SSH_MSG_DEBUG` message to the peer, which should
+    /// be shown to the user if `always_display` is set.
+    pub async fn debug(
+        &mut self,
+        message: impl Into<StringUtf8>,
+        always_display: bool,
+    ) -> Result<()> {
+        self.send(&Debug {
+            always_display: always_display.into(),
+            message: message.into(),
+            language: Default::default(),
+        })
+        .await
+    }
+
+    /// Reply to the last packet received with [`Session::recv`] with an `SSH_MSG_UNIMPLEMENTED` message,
+    /// to notify the peer that it's not supported, as described in [RFC4253 section 11.4].
+    ///
+    /// [RFC4253 section 11.4]: https://datatracker.ietf.org/doc/html/rfc4253#section-11.4
+    pub async fn unimplemented(&mut self) -> Result<()> {
+        let seq = self.state.stream()?.last_seq();
+
+        tracing::debug!("Rejecting packet #{seq} as unimplemented");
+
+        self.send(&Unimplemented { seq }).await
+    }
+
     /// Send a _disconnect message_ to the peer and shutdown the session.
     pub async fn disconnect(
         &mut self,
         reason: DisconnectReason,
         description: impl Into<StringUtf8>,
     ) -> DisconnectedError {
-        let stream = match &mut self.stream {
-            Either::Left(stream) => stream,
-            Either::Right(err) => return err.clone(),
+        let stream = match &mut self.state {
+            State::Connected(stream) => stream,
+            State::Disconnected(_, err) => return err.clone(),
+            State::Released => {
+                return DisconnectedError {
+                    by: DisconnectedBy::Us,
+                    reason,
+                    description: description.into().into_string(),
+                }
+            }
         };
 
         let message = Disconnect {
@@ -171,11 +518,38 @@ where
             reason: message.reason,
             description: message.description.into_string(),
         };
-        self.stream = Either::Right(err.clone());
+        self.state.disconnected(err.clone());
 
         err
     }
 
+    /// Gracefully shutdown the session, sending a _disconnect message_ to the peer if still connected,
+    /// then flushing and closing the underlying stream, which is returned.
+    pub async fn close(
+        mut self,
+        reason: DisconnectReason,
+        description: impl Into<StringUtf8>,
+    ) -> Result<IO> {
+        let _ = self.disconnect(reason, description).await;
+
+        let mut stream = self
+            .state
+            .release()
+            .ok_or(std::io::Error::from(std::io::ErrorKind::NotConnected))?;
+        stream.close().await?;
+
+        Ok(stream.into_inner())
+    }
+
+    /// Take the underlying stream out of the session as-is, without disconnecting
+    /// from the peer, discarding any packet that has not been written to it yet.
+    pub fn into_inner(mut self) -> Result<IO> {
+        self.state
+            .release()
+            .map(Stream::into_inner)
+            .ok_or(std::io::Error::from(std::io::ErrorKind::NotConnected).into())
+    }
+
     /// Handle a _service_ for the peer.
     pub async fn handle<H>(&mut self, mut service: H) -> Result<H::Ok<'_, IO, S>, H::Err>
     where
@@ -247,13 +621,142 @@ where
     }
 }
 
+/// Read the peer's [`Id`], skipping the lines preceding it as permitted by the RFC,
+/// refusing any line longer than `max_length` bytes.
+async fn read_id(reader: &mut (impl AsyncBufRead + Unpin), max_length: usize) -> Result<Id> {
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+
+        loop {
+            let buf = reader.fill_buf().await?;
+            if buf.is_empty() {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+
+            let (len, eol) = match buf.iter().position(|&byte| byte == b'\n') {
+                Some(position) => (position + 1, true),
+                None => (buf.len(), false),
+            };
+
+            if line.len() + len > max_length {
+                return Err(Error::LimitExceeded {
+                    what: "identification line length",
+                    value: line.len() + len,
+                    max: max_length,
+                });
+            }
+
+            line.extend_from_slice(&buf[..len]);
+            reader.consume_unpin(len);
+
+            if eol {
+                break;
+            }
+        }
+
+        if line.starts_with(b"SSH-") {
+            break Ok(String::from_utf8_lossy(&line)
+                .trim_end_matches(['\r', '\n'])
+                .parse()?);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use async_std::net::TcpStream;
+    #![allow(clippy::unwrap_used)]
+
+    use async_std::net::{TcpListener, TcpStream};
+    use futures::io::BufReader;
+    use rstest::rstest;
 
     use super::*;
     use crate::side::{client::Client, server::Server};
 
+    #[rstest]
+    #[case(b"SSH-2.0-OpenSSH_9.6\r\n", Some("OpenSSH_9.6"))]
+    #[case(
+        b"Welcome\r\nto the server\r\nSSH-2.0-OpenSSH_9.6\r\n",
+        Some("OpenSSH_9.6")
+    )]
+    #[case(
+        b"SSH-2.0-OpenSSH_9.6-with-a-very-long-softwareversion-we-refuse-to-read\r\n",
+        None
+    )]
+    #[case(
+        b"A very long line before the identification line\r\nSSH-2.0-OpenSSH_9.6\r\n",
+        None
+    )]
+    async fn it_reads_id(#[case] input: &[u8], #[case] expected: Option<&str>) {
+        let res = read_id(&mut futures::io::Cursor::new(input), 32).await;
+
+        match expected {
+            Some(softwareversion) => assert_eq!(res.unwrap().softwareversion, softwareversion),
+            None => assert!(matches!(res, Err(Error::LimitExceeded { max: 32, .. }))),
+        }
+    }
+
+    #[async_std::test]
+    async fn unimplemented_reports_the_received_packet() {
+        let socket = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let (expected, received) = futures::try_join!(
+            async {
+                let stream = BufReader::new(socket.accept().await?.0);
+                let mut server = Session::new(
+                    stream,
+                    Server {
+                        keys: vec![ssh_key::PrivateKey::random(
+                            &mut rand::thread_rng(),
+                            ssh_key::Algorithm::Ed25519,
+                        )
+                        .unwrap()],
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+                let request = server.recv().await?.to::<ServiceRequest>()?;
+                let seq = server.state.stream()?.last_seq();
+
+                // Sending while the next packet is readable peeks it from the stream.
+                server.readable().await?;
+                server
+                    .send(&ServiceAccept {
+                        service_name: request.service_name,
+                    })
+                    .await?;
+                server.unimplemented().await?;
+
+                Ok::<_, Error>(seq)
+            },
+            async {
+                let stream = BufReader::new(TcpStream::connect(addr).await?);
+                let mut client = Session::new(stream, Client::default()).await?;
+
+                for _ in 0..2 {
+                    client
+                        .send(&ServiceRequest {
+                            service_name: "ssh-userauth".into(),
+                        })
+                        .await?;
+                }
+                client.recv().await?.to::<ServiceAccept>()?;
+
+                // Bypass the session, which only logs the `SSH_MSG_UNIMPLEMENTED` messages.
+                let Unimplemented { seq } = client.state.stream()?.recv().await?.to()?;
+
+                Ok::<_, Error>(seq)
+            }
+        )
+        .unwrap();
+
+        assert_eq!(expected, received);
+    }
+
     #[test]
     fn assert_session_is_send() {
         fn is_send<T: Send>() {}
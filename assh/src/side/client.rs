@@ -7,11 +7,15 @@ use futures_time::time::Duration as Timeout;
 use rand::RngCore;
 use ssh_packet::{arch::NameList, trans::KexInit};
 
-use super::Side;
+use super::{Limits, Side};
 use crate::{
-    algorithm::{kex, Cipher, Compress, Hmac, Kex, Key},
+    algorithm::{
+        kex,
+        policy::{self, Finding},
+        Cipher, Compress, Hmac, Kex, Key, Policy,
+    },
     stream::{Stream, TransportPair},
-    Result,
+    Result, Rng,
 };
 
 #[doc(no_inline)]
@@ -28,6 +32,21 @@ pub struct Client {
     /// Timeout for sending and receiving packets.
     pub timeout: Duration,
 
+    /// Random number generator used throughout the session.
+    pub rng: Rng,
+
+    /// Whether to work around the known [`quirks`](crate::quirks)
+    /// of the peer's software when negociating algorithms.
+    pub quirks: bool,
+
+    /// Whether to enable `ssh-rsa` signatures over SHA-1 for the peers known to support
+    /// no other RSA signature algorithm, see [`Quirks::rsa_sha1_only`](crate::quirks::Quirks::rsa_sha1_only)
+    /// (disabled by default, since SHA-1 is vulnerable to collisions).
+    pub rsa_sha1_fallback: bool,
+
+    /// Limits on the input accepted from the peer.
+    pub limits: Limits,
+
     /// The algorithms enabled for this _client_ session.
     pub algorithms: Algorithms,
 }
@@ -44,6 +63,10 @@ impl Default for Client {
                 None::<&str>,
             ),
             timeout: Duration::from_secs(120),
+            rng: Default::default(),
+            quirks: true,
+            rsa_sha1_fallback: false,
+            limits: Default::default(),
             algorithms: Default::default(),
         }
     }
@@ -68,41 +91,39 @@ pub struct Algorithms {
     pub compressions: Vec<Compress>,
 }
 
-impl Default for Algorithms {
-    fn default() -> Self {
-        let super::server::Algorithms {
-            kexs,
-            ciphers,
-            macs,
-            compressions,
-        } = Default::default();
+impl Algorithms {
+    /// Validate the enabled algorithms, returning the weak ones,
+    /// or an [`Error::InsecureAlgorithms`](crate::Error::InsecureAlgorithms) if any of them is insecure.
+    pub fn validate(&self) -> Result<Vec<Finding>> {
+        policy::validate(
+            &self.kexs,
+            &self.keys,
+            &self.ciphers,
+            &self.macs,
+            &self.compressions,
+        )
+    }
+}
 
+impl From<Policy> for Algorithms {
+    fn from(policy: Policy) -> Self {
         Self {
-            kexs,
-            keys: vec![
-                Key::Ed25519,
-                Key::Ecdsa {
-                    curve: ssh_key::EcdsaCurve::NistP384,
-                },
-                Key::Ecdsa {
-                    curve: ssh_key::EcdsaCurve::NistP256,
-                },
-                Key::Rsa {
-                    hash: Some(ssh_key::HashAlg::Sha512),
-                },
-                Key::Rsa {
-                    hash: Some(ssh_key::HashAlg::Sha256),
-                },
-                Key::Rsa { hash: None },
-                Key::Dsa,
-            ],
-            ciphers,
-            macs,
-            compressions,
+            kexs: policy.kexs(),
+            keys: policy.keys(),
+            ciphers: policy.ciphers(),
+            macs: policy.macs(),
+            compressions: policy.compressions(),
         }
     }
 }
 
+/// The default [`Algorithms`] follow the [`Policy::Compatible`] preset.
+impl Default for Algorithms {
+    fn default() -> Self {
+        Policy::Compatible.into()
+    }
+}
+
 impl Side for Client {
     fn id(&self) -> &Id {
         &self.id
@@ -112,9 +133,29 @@ impl Side for Client {
         self.timeout.into()
     }
 
+    fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    fn quirks(&self) -> bool {
+        self.quirks
+    }
+
+    fn rsa_sha1_fallback(&self) -> bool {
+        self.rsa_sha1_fallback
+    }
+
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn validate(&self) -> Result<Vec<Finding>> {
+        self.algorithms.validate()
+    }
+
     fn kexinit(&self) -> KexInit {
         let mut cookie = [0u8; 16];
-        rand::thread_rng().fill_bytes(&mut cookie);
+        self.rng.clone().fill_bytes(&mut cookie);
 
         KexInit {
             cookie,
@@ -8,8 +8,10 @@ use ssh_packet::{
 };
 
 use crate::{
+    algorithm::policy::Finding,
+    quirks,
     stream::{Stream, TransportPair},
-    Result,
+    Result, Rng,
 };
 
 pub mod client;
@@ -18,6 +20,40 @@ use client::Client;
 pub mod server;
 use server::Server;
 
+/// Limits on the input accepted from the peer, to harden the session against
+/// resource exhaustion, violations cause a `SSH_DISCONNECT_PROTOCOL_ERROR` disconnect.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Maximum length of the peer's identification line, and of each line preceding it.
+    pub max_id_length: usize,
+
+    /// Maximum length of a received packet, excluding the length field and the MAC,
+    /// and of it's payload once decompressed.
+    pub max_packet_length: usize,
+
+    /// Maximum number of consecutive `SSH_MSG_IGNORE` and `SSH_MSG_DEBUG` messages,
+    /// in addition to the ones allowed by [`Limits::ignored_messages_rate`].
+    pub max_ignored_messages: usize,
+
+    /// Number of consecutive `SSH_MSG_IGNORE` and `SSH_MSG_DEBUG` messages allowed per second
+    /// elapsed since the last other message, since peers with traffic shaping enabled
+    /// keep sending them while idle, about 50 per second with the default [`TrafficShaping`].
+    ///
+    /// [`TrafficShaping`]: crate::TrafficShaping
+    pub ignored_messages_rate: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_id_length: 8192,
+            max_packet_length: ssh_packet::PACKET_MAX_SIZE,
+            max_ignored_messages: 1024,
+            ignored_messages_rate: 128,
+        }
+    }
+}
+
 mod private {
     pub trait Sealed {}
 
@@ -34,6 +70,26 @@ pub trait Side: private::Sealed {
     /// Get the _timeout_ for this session.
     fn timeout(&self) -> Duration;
 
+    /// Get the [`Rng`] for this session.
+    fn rng(&self) -> &Rng;
+
+    /// Whether to expect a [`PROXY`](crate::proxy) protocol header before the version exchange.
+    fn proxy_protocol(&self) -> bool {
+        false
+    }
+
+    /// Whether to work around the known [`quirks`](crate::quirks) of the peer.
+    fn quirks(&self) -> bool;
+
+    /// Whether to fall back to `ssh-rsa` for the peers only supporting it.
+    fn rsa_sha1_fallback(&self) -> bool;
+
+    /// Get the [`Limits`] on the input accepted from the peer.
+    fn limits(&self) -> &Limits;
+
+    /// Validate the algorithms enabled in the config.
+    fn validate(&self) -> Result<Vec<Finding>>;
+
     /// Generate a [`KexInit`] message from the config.
     fn kexinit(&self) -> KexInit;
 
@@ -55,14 +111,30 @@ pub trait Side: private::Sealed {
         async move {
             tracing::debug!("Starting key-exchange procedure");
 
-            let kexinit = self.kexinit();
+            let mut kexinit = self.kexinit();
+            if self.quirks() {
+                quirks::Software::parse(peer_id)
+                    .quirks()
+                    .adjust(&mut kexinit, self.rsa_sha1_fallback());
+            }
             stream.send(&kexinit).await?;
 
             // TODO: Take care of `KexInit::first_kex_packet_follows` being true.
 
             let peerkexinit = stream.recv().await?.to::<KexInit>()?;
 
-            let transport = self.exchange(stream, kexinit, peerkexinit, peer_id).await?;
+            let transport = match self.exchange(stream, kexinit, peerkexinit, peer_id).await {
+                Ok(transport) => transport,
+                Err(err) => {
+                    if let Some(report) = err.negotiation_report() {
+                        tracing::warn!(
+                            "Unable to negociate algorithms with peer `{peer_id}`:\n{report}"
+                        );
+                    }
+
+                    return Err(err);
+                }
+            };
 
             stream.send(&NewKeys).await?;
             stream.recv().await?.to::<NewKeys>()?;
@@ -73,7 +145,7 @@ pub trait Side: private::Sealed {
                 transport.tx,
             );
 
-            stream.with_transport(transport);
+            stream.with_transport(transport)?;
 
             Ok(())
         }
@@ -7,11 +7,15 @@ use futures_time::time::Duration as Timeout;
 use rand::RngCore;
 use ssh_packet::{arch::NameList, trans::KexInit};
 
-use super::Side;
+use super::{Limits, Side};
 use crate::{
-    algorithm::{kex, key, Cipher, Compress, Hmac, Kex},
+    algorithm::{
+        kex, key,
+        policy::{self, Finding},
+        Cipher, Compress, Hmac, Kex, Key, Policy,
+    },
     stream::{Stream, TransportPair},
-    Result,
+    Error, Result, Rng,
 };
 
 #[doc(no_inline)]
@@ -28,7 +32,27 @@ pub struct Server {
     /// Timeout for sending and receiving packets.
     pub timeout: Duration,
 
-    /// Server keys for key-exchange signature.
+    /// Random number generator used throughout the session.
+    pub rng: Rng,
+
+    /// Whether to expect a [`PROXY`](crate::proxy) protocol header
+    /// from the load-balancer before the version exchange.
+    pub proxy_protocol: bool,
+
+    /// Whether to work around the known [`quirks`](crate::quirks)
+    /// of the peer's software when negociating algorithms.
+    pub quirks: bool,
+
+    /// Whether to enable `ssh-rsa` signatures over SHA-1 for the peers known to support
+    /// no other RSA signature algorithm, see [`Quirks::rsa_sha1_only`](crate::quirks::Quirks::rsa_sha1_only)
+    /// (disabled by default, since SHA-1 is vulnerable to collisions).
+    pub rsa_sha1_fallback: bool,
+
+    /// Limits on the input accepted from the peer.
+    pub limits: Limits,
+
+    /// Server keys for key-exchange signature, at least one of them must be compatible
+    /// with the enabled [`Algorithms::keys`], or the session will fail to start.
     pub keys: Vec<PrivateKey>,
 
     /// The algorithms enabled for this _server_ session.
@@ -47,6 +71,11 @@ impl Default for Server {
                 None::<&str>,
             ),
             timeout: Duration::from_secs(120),
+            rng: Default::default(),
+            proxy_protocol: false,
+            quirks: true,
+            rsa_sha1_fallback: false,
+            limits: Default::default(),
             keys: Default::default(),
             algorithms: Default::default(),
         }
@@ -59,6 +88,14 @@ pub struct Algorithms {
     /// Enabled algorithms for _key-exchange_.
     pub kexs: Vec<Kex>,
 
+    /// Enabled algorithms for _server key signature_, only the ones
+    /// compatible with at least one of the [`Server::keys`] are advertised.
+    ///
+    /// # Note
+    /// A single RSA key is able to sign with any of the `rsa-sha2-512`, `rsa-sha2-256`
+    /// and `ssh-rsa` algorithms, the latter being disabled by default due to it's use of SHA-1.
+    pub keys: Vec<Key>,
+
     /// Enabled algorithms for _encryption & decryption_.
     pub ciphers: Vec<Cipher>,
 
@@ -69,34 +106,50 @@ pub struct Algorithms {
     pub compressions: Vec<Compress>,
 }
 
-impl Default for Algorithms {
-    fn default() -> Self {
+impl Algorithms {
+    /// Validate the enabled algorithms, returning the weak ones,
+    /// or an [`Error::InsecureAlgorithms`](crate::Error::InsecureAlgorithms) if any of them is insecure.
+    pub fn validate(&self) -> Result<Vec<Finding>> {
+        policy::validate(
+            &self.kexs,
+            &self.keys,
+            &self.ciphers,
+            &self.macs,
+            &self.compressions,
+        )
+    }
+}
+
+impl From<Policy> for Algorithms {
+    fn from(policy: Policy) -> Self {
         Self {
-            kexs: vec![Kex::Curve25519Sha256, Kex::Curve25519Sha256Libssh],
-            ciphers: vec![
-                Cipher::Aes256Ctr,
-                Cipher::Aes192Ctr,
-                Cipher::Aes128Ctr,
-                Cipher::Aes256Cbc,
-                Cipher::Aes192Cbc,
-                Cipher::Aes128Cbc,
-                Cipher::TDesCbc,
-            ],
-            macs: vec![
-                Hmac::HmacSha512ETM,
-                Hmac::HmacSha256ETM,
-                Hmac::HmacSha512,
-                Hmac::HmacSha256,
-                Hmac::HmacSha1ETM,
-                Hmac::HmacSha1,
-                Hmac::HmacMd5ETM,
-                Hmac::HmacMd5,
-            ],
-            compressions: vec![Compress::ZlibOpenssh, Compress::Zlib, Compress::None],
+            kexs: policy.kexs(),
+            keys: policy.keys(),
+            ciphers: policy.ciphers(),
+            macs: policy.macs(),
+            compressions: policy.compressions(),
         }
     }
 }
 
+/// The default [`Algorithms`] follow the [`Policy::Compatible`] preset.
+impl Default for Algorithms {
+    fn default() -> Self {
+        Policy::Compatible.into()
+    }
+}
+
+impl Server {
+    /// The enabled host key algorithms that at least one of the [`Server::keys`] can sign with.
+    fn host_key_algorithms(&self) -> impl Iterator<Item = &Key> {
+        self.algorithms.keys.iter().filter(|algorithm| {
+            self.keys
+                .iter()
+                .any(|key| key::is_compatible(&key.algorithm(), algorithm))
+        })
+    }
+}
+
 impl Side for Server {
     fn id(&self) -> &Id {
         &self.id
@@ -106,14 +159,42 @@ impl Side for Server {
         self.timeout.into()
     }
 
+    fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    fn proxy_protocol(&self) -> bool {
+        self.proxy_protocol
+    }
+
+    fn quirks(&self) -> bool {
+        self.quirks
+    }
+
+    fn rsa_sha1_fallback(&self) -> bool {
+        self.rsa_sha1_fallback
+    }
+
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn validate(&self) -> Result<Vec<Finding>> {
+        if self.host_key_algorithms().next().is_none() {
+            return Err(Error::NoUsableHostKey);
+        }
+
+        self.algorithms.validate()
+    }
+
     fn kexinit(&self) -> KexInit {
         let mut cookie = [0u8; 16];
-        rand::thread_rng().fill_bytes(&mut cookie);
+        self.rng.clone().fill_bytes(&mut cookie);
 
         KexInit {
             cookie,
             kex_algorithms: NameList::new(&self.algorithms.kexs),
-            server_host_key_algorithms: NameList::new(self.keys.iter().map(PrivateKey::algorithm)),
+            server_host_key_algorithms: NameList::new(self.host_key_algorithms()),
             encryption_algorithms_client_to_server: NameList::new(&self.algorithms.ciphers),
             encryption_algorithms_server_to_client: NameList::new(&self.algorithms.ciphers),
             mac_algorithms_client_to_server: NameList::new(&self.algorithms.macs),
@@ -137,7 +218,7 @@ impl Side for Server {
         let key = self
             .keys
             .iter()
-            .find(|key| key.algorithm() == keyalg)
+            .find(|key| key::is_compatible(&key.algorithm(), &keyalg))
             .expect("Did our KexInit lie to the client ?");
 
         kex::negociate(&peerkexinit, &kexinit)?
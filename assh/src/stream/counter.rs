@@ -17,6 +17,10 @@ impl<C> IoCounter<C> {
         }
     }
 
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
     pub fn count(&self) -> usize {
         self.rx + self.tx
     }
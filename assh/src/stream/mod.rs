@@ -1,11 +1,14 @@
 //! Primitives to manipulate binary data to extract and encode
 //! messages from/to an [`AsyncBufRead`] + [`AsyncWrite`] stream.
 
-use futures::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, FutureExt};
+use futures::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, FutureExt};
 use futures_time::{future::FutureExt as _, time::Duration};
 use ssh_packet::ToPacket;
 
-use crate::{algorithm, Result};
+use crate::{
+    algorithm::{self, CipherState},
+    keylog, Error, Result, Rng,
+};
 
 mod counter;
 use counter::IoCounter;
@@ -28,6 +31,9 @@ pub struct Stream<S> {
     inner: IoCounter<S>,
     timeout: Duration,
 
+    /// Maximum length of the received packets and of their payloads.
+    max_packet_length: usize,
+
     /// The pair of transport algorithms and keys computed from the key exchange.
     transport: TransportPair,
 
@@ -40,33 +46,105 @@ pub struct Stream<S> {
     /// Sequence number for the `rx` side.
     rxseq: u32,
 
-    /// A buffer for the `peek` method.
-    buffer: Option<Packet>,
+    /// Sequence number of the last packet yielded by the `recv` method.
+    lastseq: u32,
+
+    /// A buffer for the `peek` method, along with the packet's sequence number.
+    buffer: Option<(u32, Packet)>,
+
+    /// A reusable buffer to serialize the payloads of the sent packets.
+    payload: Vec<u8>,
+
+    /// Sealed packets that are not yet entirely written to the stream.
+    outgoing: Vec<u8>,
+
+    /// A reusable buffer to receive and decrypt packets in-place.
+    incoming: Vec<u8>,
+
+    /// The random number generator for the session.
+    rng: Rng,
+
+    /// The hook to export the key-exchange secrets to.
+    keylog: Option<Box<dyn keylog::KeyLog>>,
 }
 
 impl<S> Stream<S>
 where
     S: AsyncBufRead + AsyncWrite + Unpin,
 {
-    pub fn new(stream: S, timeout: Duration) -> Self {
+    pub fn new(stream: S, timeout: Duration, max_packet_length: usize, rng: Rng) -> Self {
         Self {
             inner: IoCounter::new(stream),
             timeout,
-            transport: Default::default(),
+            max_packet_length,
+            transport: TransportPair {
+                rx: Transport::new(rng.clone()),
+                tx: Transport::new(rng.clone()),
+            },
             session: None,
             txseq: 0,
             rxseq: 0,
+            lastseq: 0,
             buffer: None,
+            payload: Vec::new(),
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+            rng,
+            keylog: None,
         }
     }
 
+    /// Unwrap the inner stream, discarding any sealed packet that has not been written yet.
+    pub fn into_inner(self) -> S {
+        self.inner.into_inner()
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
     pub fn is_rekeyable(&self) -> bool {
         self.session.is_none() || self.inner.count() > REKEY_BYTES_THRESHOLD
     }
 
-    pub fn with_transport(&mut self, transport: TransportPair) {
+    /// Switch to the newly negociated `transport`, initializing the ciphers from the derived keys.
+    pub fn with_transport(&mut self, mut transport: TransportPair) -> Result<()> {
+        let TransportPair { rx, tx } = &mut transport;
+
+        rx.state = CipherState::decryptor(&rx.cipher, &rx.chain.key, &rx.chain.iv)?;
+        tx.state = CipherState::encryptor(&tx.cipher, &tx.chain.key, &tx.chain.iv)?;
+
         self.transport = transport;
         self.inner.reset();
+
+        Ok(())
+    }
+
+    pub fn with_keylog(&mut self, keylog: impl keylog::KeyLog + 'static) {
+        self.keylog = Some(Box::new(keylog));
+    }
+
+    /// Export the secrets of the key-exchange to the [`keylog::KeyLog`] hook if any.
+    pub fn log_keys(&mut self, hash: &[u8], secret: &[u8], client: &Transport, server: &Transport) {
+        fn direction(transport: &Transport) -> keylog::Direction<'_> {
+            keylog::Direction {
+                cipher: &transport.cipher,
+                hmac: &transport.hmac,
+                iv: &transport.chain.iv,
+                key: &transport.chain.key,
+                mac_key: &transport.chain.hmac,
+            }
+        }
+
+        if let Some(keylog) = &mut self.keylog {
+            keylog.log(&keylog::Entry {
+                session_id: self.session.as_deref().unwrap_or_default(),
+                exchange_hash: hash,
+                shared_secret: secret,
+                client_to_server: direction(client),
+                server_to_client: direction(server),
+            });
+        }
     }
 
     pub fn with_session(&mut self, session: &[u8]) -> &[u8] {
@@ -77,6 +155,11 @@ where
         self.session.as_deref()
     }
 
+    /// Sequence number of the last packet received with the `recv` method.
+    pub fn last_seq(&self) -> u32 {
+        self.lastseq
+    }
+
     pub async fn fill_buf(&mut self) -> Result<()> {
         self.inner.fill_buf().await?;
 
@@ -97,46 +180,112 @@ where
         }
     }
 
-    /// Receive and decrypt a _packet_ from the peer without removing it from the queue.
+    /// Receive and decrypt a _packet_ from the peer without removing it from the queue,
+    /// this leaves the sequence number reported by [`Self::last_seq`] untouched.
     pub async fn peek(&mut self) -> Result<&Packet> {
-        let packet = self.recv().await?;
+        let packet = match self.buffer.take() {
+            Some(buffered) => buffered,
+            None => self.read().await?,
+        };
 
-        Ok(self.buffer.insert(packet))
+        Ok(&self.buffer.insert(packet).1)
     }
 
     /// Receive and decrypt a _packet_ from the peer.
     pub async fn recv(&mut self) -> Result<Packet> {
-        match self.buffer.take() {
-            Some(packet) => Ok(packet),
-            None => {
-                let packet =
-                    Packet::from_async_reader(&mut self.inner, &mut self.transport.rx, self.rxseq)
-                        .timeout(self.timeout)
-                        .await??;
+        let (seq, packet) = match self.buffer.take() {
+            Some(buffered) => buffered,
+            None => self.read().await?,
+        };
 
-                tracing::trace!("<[rx]-({}): {} bytes", self.rxseq, packet.payload.len());
+        self.lastseq = seq;
 
-                self.rxseq = self.rxseq.wrapping_add(1);
+        Ok(packet)
+    }
 
-                Ok(packet)
-            }
+    /// Read and decrypt the next _packet_ from the stream, along with its sequence number.
+    async fn read(&mut self) -> Result<(u32, Packet)> {
+        let packet = async {
+            let rx = &mut self.transport.rx;
+            let buf = &mut self.incoming;
+
+            buf.resize(rx.cipher.block_size(), 0);
+            self.inner.read_exact(buf).await?;
+
+            let size = rx.open_head(buf, self.max_packet_length)?;
+            let head = buf.len();
+
+            buf.resize(size, 0);
+            self.inner.read_exact(&mut buf[head..]).await?;
+
+            Ok::<_, Error>(Packet {
+                payload: rx.open(buf, self.rxseq, self.max_packet_length)?,
+            })
         }
+        .timeout(self.timeout)
+        .await??;
+
+        tracing::trace!("<[rx]-({}): {} bytes", self.rxseq, packet.payload.len());
+
+        let seq = self.rxseq;
+        self.rxseq = self.rxseq.wrapping_add(1);
+
+        Ok((seq, packet))
     }
 
     /// Encrypt and send a _packet_ to the peer.
+    ///
+    /// The packet is sealed in a buffer before being written, so that cancelling this
+    /// method never leaves a partial packet behind, it is completed by the next [`Self::flush`].
     pub async fn send(&mut self, packet: &impl ToPacket) -> Result<()> {
-        let packet = packet.to_packet()?;
-
-        packet
-            .to_async_writer(&mut self.inner, &mut self.transport.tx, self.txseq)
-            .timeout(self.timeout)
-            .await??;
-        self.inner.flush().await?;
+        self.payload.clear();
+        packet.write(&mut std::io::Cursor::new(&mut self.payload))?;
+
+        let pending = self.outgoing.len();
+        if let Err(err) = self
+            .transport
+            .tx
+            .seal(&self.payload, self.txseq, &mut self.outgoing)
+        {
+            // Discard the partially sealed packet, if any.
+            self.outgoing.truncate(pending);
+
+            return Err(err);
+        }
 
-        tracing::trace!("({}) -[tx]>: {} bytes", self.txseq, packet.payload.len());
+        tracing::trace!("({}) -[tx]>: {} bytes", self.txseq, self.payload.len());
 
         self.txseq = self.txseq.wrapping_add(1);
 
+        self.flush().await
+    }
+
+    /// Write the remaining sealed packets to the peer, this method is cancel-safe.
+    pub async fn flush(&mut self) -> Result<()> {
+        while !self.outgoing.is_empty() {
+            let written = self
+                .inner
+                .write(&self.outgoing)
+                .timeout(self.timeout)
+                .await??;
+
+            if written == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+
+            self.outgoing.drain(..written);
+        }
+
+        self.inner.flush().timeout(self.timeout).await??;
+
+        Ok(())
+    }
+
+    /// Write the remaining sealed packets to the peer and close the stream.
+    pub async fn close(&mut self) -> Result<()> {
+        self.flush().await?;
+        self.inner.close().timeout(self.timeout).await??;
+
         Ok(())
     }
 }
@@ -1,33 +1,48 @@
-use rand::Rng;
+use rand::RngCore;
 use securefmt::Debug;
-use ssh_packet::{CipherCore, Mac, OpeningCipher, SealingCipher};
+use ssh_packet::{binrw, CipherCore, Mac};
 
 use crate::{
-    stream::algorithm::{self, Cipher, CipherState},
-    Error, Result,
+    stream::algorithm::{self, CipherState},
+    Error, Result, Rng as SessionRng,
 };
 
 use super::Keys;
 
 // TODO: Provide forward secrecy of keys with `zeroize`.
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TransportPair {
     pub rx: Transport,
     pub tx: Transport,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Transport {
+    pub rng: SessionRng,
     #[sensitive]
     pub chain: Keys,
     #[sensitive]
-    pub state: Option<CipherState>,
+    pub state: CipherState,
     pub cipher: algorithm::Cipher,
     pub hmac: algorithm::Hmac,
     pub compress: algorithm::Compress,
 }
 
+impl Transport {
+    /// Create an initial [`Transport`], with no encryption, integrity nor compression.
+    pub fn new(rng: SessionRng) -> Self {
+        Self {
+            rng,
+            chain: Default::default(),
+            state: CipherState::None,
+            cipher: Default::default(),
+            hmac: Default::default(),
+            compress: Default::default(),
+        }
+    }
+}
+
 impl CipherCore for Transport {
     type Err = Error;
     type Mac = algorithm::Hmac;
@@ -41,66 +56,106 @@ impl CipherCore for Transport {
     }
 }
 
-impl OpeningCipher for Transport {
-    fn decrypt<B: AsMut<[u8]>>(&mut self, mut buf: B) -> Result<(), Self::Err> {
-        if self.cipher != Cipher::None {
-            self.cipher.decrypt(
-                &mut self.state,
-                &self.chain.key,
-                &self.chain.iv,
-                buf.as_mut(),
-            )?;
+impl Transport {
+    /// Seal the `payload` into a packet appended to `buf`,
+    /// compressing, padding, encrypting in-place and authenticating it.
+    pub fn seal(&mut self, payload: &[u8], seq: u32, buf: &mut Vec<u8>) -> Result<()> {
+        let payload = self.compress.compress(payload)?;
+        let padding = self.padding(payload.len());
+        let len = std::mem::size_of::<u8>() + payload.len() + padding as usize;
+
+        let start = buf.len();
+        buf.reserve(std::mem::size_of::<u32>() + len + self.hmac.size());
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+        buf.push(padding);
+        buf.extend_from_slice(&payload);
+
+        // fill with random
+        let end = buf.len() + padding as usize;
+        buf.resize(end, 0);
+        self.rng.fill_bytes(&mut buf[end - padding as usize..]);
+
+        buf.resize(end + self.hmac.size(), 0);
+        let (packet, mac) = buf[start..].split_at_mut(end - start);
+
+        if self.hmac.etm() {
+            self.encrypt(&mut packet[std::mem::size_of::<u32>()..])?;
+            self.hmac.sign(seq, packet, &self.chain.hmac, mac);
+        } else {
+            self.hmac.sign(seq, packet, &self.chain.hmac, mac);
+            self.encrypt(packet)?;
         }
 
         Ok(())
     }
 
-    fn open<B: AsRef<[u8]>>(&mut self, buf: B, mac: Vec<u8>, seq: u32) -> Result<(), Self::Err> {
-        if self.mac().size() > 0 {
-            self.hmac
-                .verify(seq, buf.as_ref(), &self.chain.hmac, &mac)?;
+    /// Decrypt the `head` of a packet of [`CipherCore::block_size`] bytes if needed,
+    /// returning the size of the whole packet, including the MAC.
+    pub fn open_head(&mut self, head: &mut [u8], max: usize) -> Result<usize> {
+        if !self.hmac.etm() {
+            self.decrypt(head)?;
         }
 
-        Ok(())
-    }
+        let len = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+        let size = std::mem::size_of::<u32>() + len;
 
-    fn decompress(&mut self, buf: Vec<u8>) -> Result<Vec<u8>, Self::Err> {
-        self.compress.decompress(buf)
-    }
-}
+        if len > max {
+            return Err(Error::LimitExceeded {
+                what: "packet length",
+                value: len,
+                max,
+            });
+        }
+        if size < head.len() || len < std::mem::size_of::<u8>() {
+            return Err(binrw::Error::Custom {
+                pos: 0x0,
+                err: Box::new(format!("Packet size too small ({len})")),
+            }
+            .into());
+        }
 
-impl SealingCipher for Transport {
-    fn compress<B: AsRef<[u8]>>(&mut self, buf: B) -> Result<Vec<u8>, Self::Err> {
-        self.compress.compress(buf.as_ref())
+        Ok(size + self.hmac.size())
     }
 
-    fn pad(&mut self, mut buf: Vec<u8>, padding: u8) -> Result<Vec<u8>, Self::Err> {
-        let mut rng = rand::thread_rng();
-
-        // prefix with the size
-        let mut padded = vec![padding];
-        padded.append(&mut buf);
+    /// Authenticate and decrypt in-place the rest of the packet in `buf`,
+    /// which head has been processed by [`Self::open_head`], returning it's payload,
+    /// which is refused if it exceeds `max` bytes once decompressed.
+    pub fn open(&mut self, buf: &mut [u8], seq: u32, max: usize) -> Result<Vec<u8>> {
+        let (packet, mac) = buf.split_at_mut(buf.len() - self.hmac.size());
+
+        if self.hmac.etm() {
+            self.hmac.verify(seq, packet, &self.chain.hmac, mac)?;
+            self.decrypt(&mut packet[std::mem::size_of::<u32>()..])?;
+        } else {
+            let head = self.block_size();
+            self.decrypt(&mut packet[head..])?;
+            self.hmac.verify(seq, packet, &self.chain.hmac, mac)?;
+        }
 
-        // fill with random
-        padded.resize_with(padded.len() + padding as usize, || rng.gen());
+        let (padding, data) = packet[std::mem::size_of::<u32>()..]
+            .split_first()
+            .expect("Packet size has been checked in the head");
+
+        if *padding as usize > data.len() {
+            return Err(binrw::Error::Custom {
+                pos: 0x4,
+                err: Box::new(format!(
+                    "Padding size too large, {padding} > {}",
+                    data.len()
+                )),
+            }
+            .into());
+        }
 
-        Ok(padded)
+        self.compress
+            .decompress(&data[..data.len() - *padding as usize], max)
     }
 
-    fn encrypt<B: AsMut<[u8]>>(&mut self, mut buf: B) -> Result<(), Self::Err> {
-        if self.cipher != Cipher::None {
-            self.cipher.encrypt(
-                &mut self.state,
-                &self.chain.key,
-                &self.chain.iv,
-                buf.as_mut(),
-            )?;
-        }
-
-        Ok(())
+    fn encrypt(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.state.encrypt(buf)
     }
 
-    fn seal<B: AsRef<[u8]>>(&mut self, buf: B, seq: u32) -> Result<Vec<u8>, Self::Err> {
-        Ok(self.hmac.sign(seq, buf.as_ref(), &self.chain.hmac))
+    fn decrypt(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.state.decrypt(buf)
     }
 }
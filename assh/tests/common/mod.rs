@@ -3,7 +3,7 @@ use std::net::SocketAddr;
 use async_std::{net::TcpListener, stream::StreamExt};
 use futures::io::BufReader;
 
-use assh::{side::server::Server, Result, Session};
+use assh::{algorithm::Policy, side::server::Server, Result, Session};
 use ssh_packet::{
     connect::ChannelOpenConfirmation,
     trans::{Ignore, ServiceAccept},
@@ -11,18 +11,25 @@ use ssh_packet::{
 };
 
 pub async fn server() -> Result<(SocketAddr, impl futures::Future<Output = Result<Packet>>)> {
+    server_with_key(
+        ssh_key::PrivateKey::random(&mut rand::thread_rng(), ssh_key::Algorithm::Ed25519).unwrap(),
+    )
+    .await
+}
+
+pub async fn server_with_key(
+    key: ssh_key::PrivateKey,
+) -> Result<(SocketAddr, impl futures::Future<Output = Result<Packet>>)> {
     let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
     let addr = socket.local_addr()?;
 
     let handle = async_std::task::spawn_local(async move {
         let stream = BufReader::new(socket.incoming().next().await.unwrap()?);
 
+        // Enable every algorithm, to be tested against the peer.
         let server = Server {
-            keys: vec![ssh_key::PrivateKey::random(
-                &mut rand::thread_rng(),
-                ssh_key::Algorithm::Ed25519,
-            )
-            .unwrap()],
+            keys: vec![key],
+            algorithms: Policy::Legacy.into(),
             ..Default::default()
         };
         let mut session = Session::new(stream, server).await?;
@@ -58,3 +58,40 @@ async fn against_openssh_client(
 
     Ok(())
 }
+
+#[rstest]
+#[case("rsa-sha2-512")]
+#[case("rsa-sha2-256")]
+async fn against_openssh_client_with_rsa(
+    #[case] hostkey: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .try_init()
+        .ok();
+
+    let (addr, handle) = common::server_with_key(ssh_key::PrivateKey::from_openssh(include_str!(
+        "common/rsa"
+    ))?)
+    .await?;
+
+    tracing::info!("hostkey::{hostkey}, bound to {addr}");
+
+    let mut client = Command::new("ssh")
+        .arg("-oStrictHostKeyChecking=no")
+        .arg("-oUserKnownHostsFile=/dev/null")
+        .arg(format!("-oHostKeyAlgorithms={hostkey}"))
+        .arg(format!("-p{}", addr.port()))
+        .arg("user@127.0.0.1")
+        .arg("/bin/bash")
+        .spawn()?;
+
+    let message = handle.await?;
+    let status = client.status().await?;
+
+    tracing::info!("message: {message:?}, {status}");
+
+    assert!(matches!(message.to()?, Message::ChannelRequest { .. }));
+
+    Ok(())
+}
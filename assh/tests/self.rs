@@ -1,16 +1,29 @@
 #![allow(clippy::unwrap_used)]
 
-use async_std::net::TcpStream;
-use futures::io::BufReader;
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use async_std::net::{TcpListener, TcpStream};
+use futures::{io::BufReader, AsyncRead, AsyncWrite};
+use rand::{rngs::StdRng, SeedableRng};
 use rstest::rstest;
 
 use assh::{
-    side::client::{Algorithms, Client},
-    Error, Result, Session,
+    algorithm::{report::Outcome, Cipher, Policy},
+    error::DisconnectedBy,
+    side::{
+        client::{Algorithms, Client},
+        server::Server,
+        Limits,
+    },
+    Error, Result, Rng, Session, TrafficShaping,
 };
 use ssh_packet::{
     connect::{ChannelOpen, ChannelOpenContext},
-    trans::{Disconnect, ServiceRequest},
+    trans::{Disconnect, DisconnectReason, ServiceAccept, ServiceRequest},
     userauth, Message,
 };
 
@@ -109,3 +122,559 @@ async fn end_to_end(
 
     Ok(())
 }
+
+#[async_std::test]
+async fn traffic_shaping_and_debug_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = socket.local_addr()?;
+
+    let displayed = Arc::new(Mutex::new(Vec::new()));
+
+    futures::try_join!(
+        async {
+            let stream = BufReader::new(socket.accept().await?.0);
+            let mut server = Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            let log = displayed.clone();
+            server.on_debug(move |message| log.lock().unwrap().push(message.to_string()));
+
+            let request = server.recv().await?.to::<ServiceRequest>()?;
+
+            // Let the client idle for a while, sending chaff packets.
+            async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+
+            server
+                .send(&ServiceAccept {
+                    service_name: request.service_name,
+                })
+                .await
+        },
+        async {
+            let stream = BufReader::new(TcpStream::connect(addr).await?);
+            let mut client = Session::new(stream, Client::default()).await?;
+
+            client.traffic_shaping(TrafficShaping {
+                idle_interval: std::time::Duration::from_millis(5),
+                ..Default::default()
+            });
+
+            client.ignore(32).await?;
+            client.debug("shown", true).await?;
+            client.debug("hidden", false).await?;
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await?;
+
+            client.readable().await?;
+            client.recv().await?.to::<ServiceAccept>()?;
+
+            Ok::<_, Error>(())
+        }
+    )?;
+
+    assert_eq!(*displayed.lock().unwrap(), ["shown"]);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn traffic_shaping_stops_when_idle() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let (mut server, mut client) = pair(
+        &socket,
+        Limits {
+            max_ignored_messages: 32,
+            ignored_messages_rate: 0,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    client.traffic_shaping(TrafficShaping {
+        idle_interval: std::time::Duration::from_millis(5),
+        idle_period: std::time::Duration::from_millis(50),
+        ..Default::default()
+    });
+
+    futures::try_join!(
+        async {
+            server.recv().await?.to::<ServiceRequest>()?;
+
+            // Idle for long enough to exceed the limit, would the client keep sending chaff.
+            async_std::task::sleep(std::time::Duration::from_millis(500)).await;
+
+            server
+                .send(&ServiceAccept {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await?;
+            server.recv().await?.to::<ServiceRequest>()?;
+
+            Ok::<_, Error>(())
+        },
+        async {
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await?;
+
+            client.readable().await?;
+            client.recv().await?.to::<ServiceAccept>()?;
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await
+        }
+    )?;
+
+    Ok(())
+}
+
+async fn pair(
+    socket: &TcpListener,
+    limits: Limits,
+) -> Result<(
+    Session<BufReader<TcpStream>, Server>,
+    Session<BufReader<TcpStream>, Client>,
+)> {
+    let addr = socket.local_addr()?;
+
+    let (mut server, mut client) = futures::try_join!(
+        async {
+            let stream = BufReader::new(socket.accept().await?.0);
+            Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    limits,
+                    ..Default::default()
+                },
+            )
+            .await
+        },
+        async {
+            let stream = BufReader::new(TcpStream::connect(addr).await?);
+            Session::new(stream, Client::default()).await
+        }
+    )?;
+
+    // Complete the key-exchange on both sides.
+    futures::try_join!(
+        async {
+            let request = server.recv().await?.to::<ServiceRequest>()?;
+            server
+                .send(&ServiceAccept {
+                    service_name: request.service_name,
+                })
+                .await
+        },
+        async {
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await?;
+            client.recv().await?.to::<ServiceAccept>()?;
+
+            Ok(())
+        }
+    )?;
+
+    Ok((server, client))
+}
+
+#[async_std::test]
+async fn close_returns_the_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let (mut server, client) = pair(&socket, Default::default()).await?;
+
+    let stream = client
+        .close(DisconnectReason::ByApplication, "Goodbye")
+        .await?;
+    assert_eq!(stream.get_ref().peer_addr()?, socket.local_addr()?);
+    drop(stream);
+
+    let Err(Error::Disconnected(err)) = server.recv().await else {
+        panic!("Expected the session to be disconnected");
+    };
+    assert!(matches!(err.by, DisconnectedBy::Them));
+    assert_eq!(err.description, "Goodbye");
+
+    // The stream is kept after disconnection and released as-is, to observe the connection's end.
+    let mut stream = server.into_inner()?;
+    assert_eq!(futures::AsyncReadExt::read(&mut stream, &mut [0]).await?, 0);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(Some(DisconnectReason::ByApplication))]
+#[case(None)]
+async fn drop_disconnects(
+    #[case] reason: Option<DisconnectReason>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let (mut server, mut client) = pair(&socket, Default::default()).await?;
+
+    server.disconnect_on_drop(reason.clone());
+    drop(server);
+
+    match (client.recv().await, reason) {
+        (Err(Error::Disconnected(err)), Some(_)) => {
+            assert!(matches!(err.by, DisconnectedBy::Them));
+            assert!(matches!(err.reason, DisconnectReason::ByApplication));
+        }
+        (Err(Error::Io(err)), None) => {
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof)
+        }
+        (res, _) => panic!("Unexpected outcome: {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn negotiation_failure_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = socket.local_addr()?;
+
+    let (server, client) = futures::join!(
+        async {
+            let stream = BufReader::new(socket.accept().await?.0);
+            let mut server = Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            server.recv().await
+        },
+        async {
+            let stream = BufReader::new(TcpStream::connect(addr).await?);
+            let mut client = Session::new(
+                stream,
+                Client {
+                    algorithms: Algorithms {
+                        ciphers: vec![Cipher::Aes128Cbc],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            client.recv().await
+        }
+    );
+
+    for err in [server.unwrap_err(), client.unwrap_err()] {
+        let Some(report) = err.negotiation_report() else {
+            panic!("Expected a negotiation report, got: {err}");
+        };
+
+        assert!(matches!(report.cipher.0, Outcome::NoCommon(_)));
+        assert!(report.kex.is_negotiated() && report.key.is_negotiated());
+        assert!(err.is_misconfiguration());
+    }
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn unusable_host_keys_are_refused() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = socket.local_addr()?;
+
+    let (server, _) = futures::join!(
+        async {
+            let stream = BufReader::new(socket.accept().await?.0);
+
+            // The FIPS preset has no algorithm able to sign with an `ssh-ed25519` key.
+            Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    algorithms: Policy::Fips.into(),
+                    ..Default::default()
+                },
+            )
+            .await
+        },
+        TcpStream::connect(addr)
+    );
+
+    let err = server.err().unwrap();
+    assert!(matches!(err, Error::NoUsableHostKey));
+    assert!(err.is_misconfiguration());
+
+    Ok(())
+}
+
+#[rstest]
+#[case::ignored_messages(Limits { max_ignored_messages: 4, ignored_messages_rate: 0, ..Default::default() }, 5, 0)]
+#[case::packet_length(Limits { max_packet_length: 1024, ..Default::default() }, 1, 2048)]
+async fn limits_are_enforced(
+    #[case] limits: Limits,
+    #[case] count: usize,
+    #[case] length: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let (mut server, mut client) = pair(&socket, limits).await?;
+
+    for _ in 0..count {
+        client.ignore(length).await?;
+    }
+
+    let Err(Error::Disconnected(err)) = server.recv().await else {
+        panic!("Expected the session to be disconnected");
+    };
+    assert!(matches!(err.by, DisconnectedBy::Us));
+    assert!(matches!(err.reason, DisconnectReason::ProtocolError));
+
+    let Err(Error::Disconnected(err)) = client.recv().await else {
+        panic!("Expected the session to be disconnected");
+    };
+    assert!(matches!(err.by, DisconnectedBy::Them));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn idle_traffic_shaping_is_within_limits() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let (mut server, mut client) = pair(
+        &socket,
+        Limits {
+            max_ignored_messages: 8,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let shaping = TrafficShaping {
+        idle_period: std::time::Duration::from_secs(10),
+        ..Default::default()
+    };
+    server.traffic_shaping(shaping.clone());
+    client.traffic_shaping(shaping);
+
+    futures::try_join!(
+        async {
+            // Stay blocked while the client idles, for longer than the limit alone would allow.
+            async_std::task::sleep(std::time::Duration::from_secs(1)).await;
+
+            server
+                .send(&ServiceAccept {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await?;
+            server.recv().await?.to::<ServiceRequest>()?;
+
+            Ok::<_, Error>(())
+        },
+        async {
+            client.readable().await?;
+            client.recv().await?.to::<ServiceAccept>()?;
+
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await
+        }
+    )?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn keylog_is_consistent() -> Result<(), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = socket.local_addr()?;
+
+    let (serverlog, clientlog) = (
+        Arc::new(Mutex::new(Vec::new())),
+        Arc::new(Mutex::new(Vec::new())),
+    );
+
+    futures::try_join!(
+        async {
+            let stream = BufReader::new(socket.accept().await?.0);
+            let mut server = Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut rand::thread_rng(),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            let log = serverlog.clone();
+            server.keylog(move |entry: &assh::keylog::Entry| {
+                log.lock().unwrap().push(entry.to_string())
+            });
+
+            server.recv().await
+        },
+        async {
+            let stream = BufReader::new(TcpStream::connect(addr).await?);
+            let mut client = Session::new(stream, Client::default()).await?;
+
+            let log = clientlog.clone();
+            client.keylog(move |entry: &assh::keylog::Entry| {
+                log.lock().unwrap().push(entry.to_string())
+            });
+
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await
+        }
+    )?;
+
+    let (serverlog, clientlog) = (serverlog.lock().unwrap(), clientlog.lock().unwrap());
+
+    assert_eq!(serverlog.len(), 1);
+    assert_eq!(*serverlog, *clientlog);
+
+    Ok(())
+}
+
+/// A wrapper around a stream, recording all the bytes written to it.
+struct Recorder<S> {
+    inner: S,
+    record: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Recorder<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Recorder<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(size)) = poll {
+            self.record.lock().unwrap().extend_from_slice(&buf[..size]);
+        }
+
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+async fn transcript(seed: u64) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    let socket = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let addr = socket.local_addr()?;
+
+    let (serverbytes, clientbytes) = Default::default();
+
+    futures::try_join!(
+        async {
+            let stream = BufReader::new(Recorder {
+                inner: socket.accept().await?.0,
+                record: Arc::clone(&serverbytes),
+            });
+            let mut server = Session::new(
+                stream,
+                Server {
+                    keys: vec![ssh_key::PrivateKey::random(
+                        &mut StdRng::seed_from_u64(seed),
+                        ssh_key::Algorithm::Ed25519,
+                    )
+                    .unwrap()],
+                    rng: Rng::new(StdRng::seed_from_u64(seed)),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            server.recv().await
+        },
+        async {
+            let stream = BufReader::new(Recorder {
+                inner: TcpStream::connect(addr).await?,
+                record: Arc::clone(&clientbytes),
+            });
+            let mut client = Session::new(
+                stream,
+                Client {
+                    rng: Rng::new(StdRng::seed_from_u64(seed)),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+            client
+                .send(&ServiceRequest {
+                    service_name: "ssh-userauth".into(),
+                })
+                .await
+        }
+    )?;
+
+    let serverbytes = serverbytes.lock().unwrap().clone();
+    let clientbytes = clientbytes.lock().unwrap().clone();
+
+    Ok((serverbytes, clientbytes))
+}
+
+#[async_std::test]
+async fn seeded_sessions_are_reproducible() -> Result<(), Box<dyn std::error::Error>> {
+    assert_eq!(transcript(0xdead).await?, transcript(0xdead).await?);
+    assert_ne!(transcript(0xdead).await?, transcript(0xbeef).await?);
+
+    Ok(())
+}
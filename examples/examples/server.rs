@@ -4,13 +4,9 @@ use assh::{side::server::Server, Session};
 use assh_auth::handler::{none, Auth};
 use assh_connect::{channel, connect::channel::Outcome};
 
-use async_compat::CompatExt;
 use clap::Parser;
 use color_eyre::eyre;
-use futures::{
-    io::{BufReader, BufWriter},
-    AsyncReadExt, AsyncWriteExt, FutureExt,
-};
+use futures::{AsyncReadExt, AsyncWriteExt, FutureExt};
 use tokio::{net::TcpListener, task};
 
 // TODO: Create a kind-of complete server-side example.
@@ -58,9 +54,7 @@ async fn main() -> eyre::Result<()> {
         let (stream, _addr) = listener.accept().await?;
         let keys = keys.clone();
         task::spawn(async move {
-            let stream = BufReader::new(BufWriter::new(stream.compat()));
-
-            let mut session = Session::new(
+            let mut session = Session::new_tokio(
                 stream,
                 Server {
                     keys,
@@ -71,7 +65,7 @@ async fn main() -> eyre::Result<()> {
 
             tracing::info!("Successfully connected to `{}`", session.peer_id());
 
-            let connect = session
+            let (identity, connect) = session
                 .handle(
                     Auth::new(assh_connect::Service)
                         .banner("Welcome, and get parrot'd\r\n")
@@ -79,6 +73,8 @@ async fn main() -> eyre::Result<()> {
                 )
                 .await?;
 
+            tracing::info!("Successfully authenticated as `{}`", identity.username);
+
             connect
                 .on_channel_open(|_, channel: channel::Channel| {
                     task::spawn(async move {